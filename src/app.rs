@@ -1,14 +1,86 @@
 use eframe::egui;
-use crate::{password, stb_image, totp, vault, base32::b32encode, sys};
-use std::path::PathBuf;
+use crate::{clipboard, password, settings::Settings, stb_image, totp, vault, base32::{b32decode, b32encode}, hmac::Algorithm, otpauth::{self, OtpKind}, sys};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 use rfd::FileDialog;
+use zeroize::{Zeroize, Zeroizing};
 
 const ICON_DIM: f32 = 28.0;
 
+// Passwords are allocated with this capacity, so typing them doesn't reallocate the buffer and
+// leave copies behind that wouldn't be wiped.
+const PASSWORD_CAPACITY: usize = 256;
+
+fn password_buffer() -> Zeroizing<String> {
+    return Zeroizing::new(String::with_capacity(PASSWORD_CAPACITY));
+}
+
+// The next code is shown next to the current one when it expires in less than this, so a code
+// that would expire before the server accepts it isn't copied.
+const SHOW_NEXT_CODE_BEFORE: Duration = Duration::from_secs(5);
+
+// How long an error stays at the bottom of the window.
+const NOTIFICATION_DURATION: Duration = Duration::from_secs(8);
+
+/// Error shown to the user for a while, as release builds on Windows don't have a console where
+/// the errors printed on stderr would be visible.
+struct Notification {
+    message: String,
+    shown_until: Instant,
+}
+
+// Querying whether the screen is locked may go through the session bus, so it isn't done every
+// frame.
+const SCREEN_LOCK_CHECK_INTERVAL: Duration = Duration::from_secs(2);
+
+// Shown in place of the URL and the secret in privacy mode, until the user reveals them.
+const SECRET_MASK: &str = "••••••••••••••••";
+
+/// Mask a code in privacy mode, keeping its length so the grid doesn't move when it's revealed.
+fn mask_code(code: &str) -> String {
+    let len = code.chars().count();
+    let first = "•".repeat(len.div_ceil(2));
+    let second = "•".repeat(len / 2);
+    return format!("{} {}", first, second);
+}
+
+/// Master password asked in the details window before revealing the secret in privacy mode.
+struct RevealPrompt {
+    select: bool,
+    failure: bool,
+    submitted: bool,
+    password: Zeroizing<String>,
+}
+
+impl RevealPrompt {
+    fn new() -> Self {
+        return RevealPrompt {
+            select: true,
+            failure: false,
+            submitted: false,
+            password: password_buffer(),
+        };
+    }
+
+    fn failed(&mut self) {
+        self.select = true;
+        self.failure = true;
+        self.submitted = false;
+        self.password.zeroize();
+    }
+}
+
 struct Row {
     secret: vault::VaultSecret,
     editing: bool,
     show_details: bool,
+    // Privacy mode only, the code is shown while hovered or after a click.
+    show_code: bool,
+    code_hovered: bool,
+    // Privacy mode only, the URL and the secret are shown after the master password is entered.
+    revealed: bool,
+    reveal_prompt: Option<RevealPrompt>,
 }
 
 impl Row {
@@ -17,14 +89,40 @@ impl Row {
             secret,
             editing: false,
             show_details: false,
+            show_code: false,
+            code_hovered: false,
+            revealed: false,
+            reveal_prompt: None,
+        };
+    }
+
+    /// Draw a code, masked in privacy mode until it's hovered or clicked.
+    fn draw_code(&mut self, ui: &mut egui::Ui, code: &str, privacy_mode: bool) {
+        if !privacy_mode {
+            ui.label(code);
+            return;
+        }
+
+        let text = if self.show_code || self.code_hovered {
+            code.to_string()
+        } else {
+            mask_code(code)
         };
+
+        let label = egui::Label::new(text).sense(egui::Sense::click());
+        let response = ui.add(label).on_hover_text("Click to keep the code shown");
+        if response.clicked() {
+            self.show_code = !self.show_code;
+        }
+
+        self.code_hovered = response.hovered();
     }
 }
 
 enum Db {
     None,
     Path(PathBuf),
-    Opened,
+    Opened(Box<vault::Vault>),
 }
 
 impl Db {
@@ -35,35 +133,68 @@ impl Db {
 
 struct App {
     password_modal: Option<PasswordWindow>,
+    new_vault_modal: Option<NewVaultWindow>,
+    add_secret_modal: Option<AddSecretWindow>,
+    import_modal: Option<ImportWindow>,
+    settings_window: Option<SettingsWindow>,
+    region_window: Option<RegionWindow>,
     dropped_files: Vec<egui::DroppedFile>,
+    settings: Settings,
 
     database: Db,
+    keyfile: Option<PathBuf>,
     rows: Vec<Row>,
+    collapsed_groups: HashSet<Vec<vault::VaultGroup>>,
+    new_group: String,
+    filter: String,
+    focus_filter: bool,
     icon_textures: Vec<egui::TextureHandle>,
+    unsaved_changes: bool,
+    last_activity: Instant,
+    last_screen_lock_check: Instant,
+    // Minimizing the window and locking the screen lock the vault when they happen, so a lock
+    // that failed isn't retried every frame.
+    was_minimized: bool,
+    screen_was_locked: bool,
+    copied: Option<clipboard::CopiedText>,
+    notifications: Vec<Notification>,
 }
 
 struct PasswordWindow {
     select: bool,
     failure: bool,
-    password: String,
+    password: Zeroizing<String>,
+    keyfile: Option<PathBuf>,
 }
 
 impl PasswordWindow {
-    pub fn open() -> Self {
+    pub fn open(keyfile: Option<PathBuf>) -> Self {
         return Self {
             select: true,
             failure: false,
-            password: String::new(),
+            password: password_buffer(),
+            keyfile,
         };
     }
 
+    /// Password to open the database with. An empty password with a key file means that the
+    /// database is protected by the key file only.
+    pub fn password(&self) -> Option<&str> {
+        if self.password.is_empty() && self.keyfile.is_some() {
+            return None;
+        } else {
+            return Some(self.password.as_str());
+        }
+    }
+
     pub fn failed(mut self) -> Self {
         self.select = true;
         self.failure = true;
         return self;
     }
 
-    pub fn show(&mut self, ctx: &egui::Context) -> Option<String> {
+    /// Returns true once the user entered the password, which is then read with `password`.
+    pub fn show(&mut self, ctx: &egui::Context) -> bool {
         let mut is_open = true;
         let mut close_after = false;
         egui::Window::new("Password input")
@@ -83,6 +214,25 @@ impl PasswordWindow {
                     close_after = true;
                 }
 
+                ui.horizontal(|ui| {
+                    if ui.button("Key file").clicked() {
+                        if let Some(path) = FileDialog::new().pick_file() {
+                            self.keyfile = Some(path);
+                        }
+                    }
+
+                    if let Some(keyfile) = &self.keyfile {
+                        let name = keyfile.file_name().unwrap_or_default().to_string_lossy();
+                        ui.add(egui::Label::new(name).truncate(true))
+                            .on_hover_text(format!("{}", keyfile.display()));
+                        if ui.small_button("✖").on_hover_text("Don't use a key file").clicked() {
+                            self.keyfile = None;
+                        }
+                    } else {
+                        ui.weak("No key file");
+                    }
+                });
+
                 ui.with_layout(egui::Layout::left_to_right(egui::Align::Center), |ui| {
                     let mut size = ui.available_size();
                     size.x /= 2.0;
@@ -92,24 +242,523 @@ impl PasswordWindow {
                     }
 
                     if ui.add_sized(ui.available_size(), egui::Button::new("Cancel")).clicked() {
-                        self.password.clear();
+                        self.password.zeroize();
                         close_after = true;
                     }
                 });
 
                 if ui.input(|i| i.key_pressed(egui::Key::Escape)) {
-                    self.password.clear();
+                    self.password.zeroize();
                     close_after = true;
                 }
 
                 self.select = false;
             });
 
-        if close_after || !is_open {
-            return Some(self.password.clone());
+        return close_after || !is_open;
+    }
+}
+
+struct NewVaultWindow {
+    path: PathBuf,
+    password: Zeroizing<String>,
+    confirmation: Zeroizing<String>,
+    error: Option<&'static str>,
+}
+
+enum NewVaultAction {
+    None,
+    Create,
+    Cancel,
+}
+
+impl NewVaultWindow {
+    pub fn open(path: PathBuf) -> Self {
+        return Self {
+            path,
+            password: password_buffer(),
+            confirmation: password_buffer(),
+            error: None,
+        };
+    }
+
+    pub fn show(&mut self, ctx: &egui::Context) -> NewVaultAction {
+        let mut action = NewVaultAction::None;
+        egui::Window::new("New vault")
+            .anchor(egui::Align2::CENTER_CENTER, egui::vec2(0.0, 0.0))
+            .collapsible(false)
+            .resizable(false)
+            .fixed_size(egui::vec2(240.0, 15.0))
+            .show(ctx, |ui| {
+                ui.label(format!("{}", self.path.display()));
+
+                ui.label("Master password:");
+                ui.push_id("password", |ui| {
+                    ui.add(password::password(&mut self.password, self.error.is_some()));
+                });
+
+                ui.label("Confirm master password:");
+                ui.push_id("confirmation", |ui| {
+                    ui.add(password::password(&mut self.confirmation, self.error.is_some()));
+                });
+
+                if let Some(error) = self.error {
+                    ui.colored_label(egui::Color32::LIGHT_RED, error);
+                }
+
+                ui.with_layout(egui::Layout::left_to_right(egui::Align::Center), |ui| {
+                    let mut size = ui.available_size();
+                    size.x /= 2.0;
+
+                    if ui.add_sized(size, egui::Button::new("Create")).clicked() {
+                        action = NewVaultAction::Create;
+                    }
+
+                    if ui.add_sized(ui.available_size(), egui::Button::new("Cancel")).clicked() {
+                        action = NewVaultAction::Cancel;
+                    }
+                });
+
+                if ui.input(|i| i.key_pressed(egui::Key::Escape)) {
+                    action = NewVaultAction::Cancel;
+                }
+            });
+
+        if let NewVaultAction::Create = action {
+            if self.password.is_empty() {
+                self.error = Some("The master password can't be empty");
+                return NewVaultAction::None;
+            } else if self.password != self.confirmation {
+                self.error = Some("The passwords don't match");
+                return NewVaultAction::None;
+            }
+        }
+
+        return action;
+    }
+}
+
+struct AddSecretWindow {
+    issuer: String,
+    account_name: String,
+    secret: Zeroizing<String>,
+    period: u64,
+    digits: usize,
+    algorithm: Algorithm,
+    error: Option<String>,
+}
+
+enum AddSecretAction {
+    None,
+    Add(otpauth::ParsedUrl),
+    Cancel,
+}
+
+impl AddSecretWindow {
+    pub fn open() -> Self {
+        return Self {
+            issuer: String::new(),
+            account_name: String::new(),
+            secret: password_buffer(),
+            period: 30,
+            digits: 6,
+            algorithm: Algorithm::default(),
+            error: None,
+        };
+    }
+
+    pub fn show(&mut self, ctx: &egui::Context) -> AddSecretAction {
+        let mut add = false;
+        let mut cancel = false;
+        egui::Window::new("Add manually")
+            .anchor(egui::Align2::CENTER_CENTER, egui::vec2(0.0, 0.0))
+            .collapsible(false)
+            .resizable(false)
+            .fixed_size(egui::vec2(280.0, 15.0))
+            .show(ctx, |ui| {
+                egui::Grid::new("add_secret_grid").num_columns(2).show(ui, |ui| {
+                    ui.label("Issuer:");
+                    ui.text_edit_singleline(&mut self.issuer);
+                    ui.end_row();
+
+                    ui.label("Account:");
+                    ui.text_edit_singleline(&mut self.account_name);
+                    ui.end_row();
+
+                    ui.label("Secret:");
+                    let text_edit = egui::TextEdit::singleline(&mut *self.secret).hint_text("Base32, e.g. JBSW Y3DP");
+                    let response = ui.add(text_edit);
+                    password::protect_field(ui.ctx(), response.id);
+                    ui.end_row();
+
+                    ui.label("Period:");
+                    ui.add(egui::DragValue::new(&mut self.period).clamp_range(1..=300).suffix("s"));
+                    ui.end_row();
+
+                    ui.label("Digits:");
+                    ui.add(egui::DragValue::new(&mut self.digits).clamp_range(6..=10));
+                    ui.end_row();
+
+                    ui.label("Algorithm:");
+                    egui::ComboBox::from_id_source("add_secret_algorithm")
+                        .selected_text(self.algorithm.name())
+                        .show_ui(ui, |ui| {
+                            for algorithm in [Algorithm::Sha1, Algorithm::Sha256, Algorithm::Sha512] {
+                                ui.selectable_value(&mut self.algorithm, algorithm, algorithm.name());
+                            }
+                        });
+                    ui.end_row();
+                });
+
+                if let Some(error) = &self.error {
+                    ui.colored_label(egui::Color32::LIGHT_RED, error.as_str());
+                }
+
+                ui.with_layout(egui::Layout::left_to_right(egui::Align::Center), |ui| {
+                    let mut size = ui.available_size();
+                    size.x /= 2.0;
+
+                    if ui.add_sized(size, egui::Button::new("Add")).clicked() {
+                        add = true;
+                    }
+
+                    if ui.add_sized(ui.available_size(), egui::Button::new("Cancel")).clicked() {
+                        cancel = true;
+                    }
+                });
+
+                if ui.input(|i| i.key_pressed(egui::Key::Escape)) {
+                    cancel = true;
+                }
+            });
+
+        if cancel {
+            return AddSecretAction::Cancel;
+        } else if !add {
+            return AddSecretAction::None;
+        }
+
+        let secret = Zeroizing::new(self.secret.split_whitespace().collect::<String>());
+        if self.issuer.trim().is_empty() {
+            self.error = Some("The issuer can't be empty".to_string());
+        } else if self.issuer.contains(':') {
+            self.error = Some("The issuer can't contain ':'".to_string());
+        } else if secret.is_empty() {
+            self.error = Some("The secret can't be empty".to_string());
+        } else if b32decode(secret.as_bytes()).map(Zeroizing::new).is_err() {
+            self.error = Some("The secret isn't valid base32".to_string());
         } else {
-            return None;
+            let parsed_url = otpauth::ParsedUrl::from_parts(
+                self.issuer.trim(),
+                self.account_name.trim(),
+                secret.as_str(),
+                self.period,
+                self.digits,
+                self.algorithm,
+            );
+
+            match parsed_url {
+                Ok(parsed_url) => return AddSecretAction::Add(parsed_url),
+                Err(err) => self.error = Some(format!("The secret couldn't be added, {}", err)),
+            }
+        }
+
+        return AddSecretAction::None;
+    }
+}
+
+/// Secrets read from an image with several QR codes, the user picks the ones to add.
+struct ImportWindow {
+    secrets: Vec<(bool, vault::VaultSecret)>,
+    /// Position of the QR codes that couldn't be read, counted from 1, and why.
+    failures: Vec<(usize, vault::Error)>,
+}
+
+enum ImportAction {
+    None,
+    Add(Vec<vault::VaultSecret>),
+    Cancel,
+}
+
+impl ImportWindow {
+    pub fn open(results: Vec<Result<vault::VaultSecret, vault::Error>>) -> Self {
+        let mut secrets = Vec::new();
+        let mut failures = Vec::new();
+        for (idx, result) in results.into_iter().enumerate() {
+            match result {
+                Ok(secret) => secrets.push((true, secret)),
+                Err(err) => failures.push((idx + 1, err)),
+            }
         }
+
+        return Self {
+            secrets,
+            failures,
+        };
+    }
+
+    pub fn show(&mut self, ctx: &egui::Context) -> ImportAction {
+        let mut action = ImportAction::None;
+        egui::Window::new("Import secrets")
+            .anchor(egui::Align2::CENTER_CENTER, egui::vec2(0.0, 0.0))
+            .collapsible(false)
+            .resizable(false)
+            .fixed_size(egui::vec2(280.0, 15.0))
+            .show(ctx, |ui| {
+                ui.label(format!("{} QR codes were found:", self.secrets.len() + self.failures.len()));
+
+                egui::ScrollArea::vertical().max_height(240.0).show(ui, |ui| {
+                    for (selected, secret) in self.secrets.iter_mut() {
+                        ui.checkbox(selected, secret.name.as_str());
+                    }
+
+                    for (position, err) in self.failures.iter() {
+                        let error = format!("QR code {} couldn't be read, {}", position, err);
+                        ui.colored_label(egui::Color32::LIGHT_RED, error);
+                    }
+                });
+
+                ui.with_layout(egui::Layout::left_to_right(egui::Align::Center), |ui| {
+                    let mut size = ui.available_size();
+                    size.x /= 2.0;
+
+                    let any_selected = self.secrets.iter().any(|(selected, _)| *selected);
+                    let button = ui.add_enabled_ui(any_selected, |ui| {
+                        return ui.add_sized(size, egui::Button::new("Add selected"));
+                    });
+                    if button.inner.clicked() {
+                        let secrets = std::mem::take(&mut self.secrets);
+                        let secrets = secrets.into_iter().filter(|(selected, _)| *selected).map(|(_, secret)| secret);
+                        action = ImportAction::Add(secrets.collect());
+                    }
+
+                    if ui.add_sized(ui.available_size(), egui::Button::new("Cancel")).clicked() {
+                        action = ImportAction::Cancel;
+                    }
+                });
+
+                if ui.input(|i| i.key_pressed(egui::Key::Escape)) {
+                    action = ImportAction::Cancel;
+                }
+            });
+
+        return action;
+    }
+}
+
+/// Capture of the screen, in which the user drags a rectangle around the QR code to decode, when
+/// the whole screen contains several QR codes or is too large for the detection.
+struct RegionWindow {
+    width: usize,
+    height: usize,
+    pixels: Vec<u8>,
+    texture: Option<egui::TextureHandle>,
+    drag_start: Option<egui::Pos2>,
+    error: Option<&'static str>,
+}
+
+enum RegionAction {
+    None,
+    /// Width, height and greyscale pixels of the selected region.
+    Select(usize, usize, Vec<u8>),
+    Cancel,
+}
+
+impl RegionWindow {
+    pub fn open(width: usize, height: usize, pixels: Vec<u8>) -> Self {
+        return Self {
+            width,
+            height,
+            pixels,
+            texture: None,
+            drag_start: None,
+            error: None,
+        };
+    }
+
+    /// Pixels of the capture inside `rect`, given in pixels of the capture.
+    fn crop(&self, rect: egui::Rect) -> (usize, usize, Vec<u8>) {
+        let min_x = (rect.min.x.max(0.0) as usize).min(self.width);
+        let min_y = (rect.min.y.max(0.0) as usize).min(self.height);
+        let max_x = (rect.max.x.max(0.0).ceil() as usize).min(self.width);
+        let max_y = (rect.max.y.max(0.0).ceil() as usize).min(self.height);
+
+        let mut pixels = Vec::with_capacity((max_x - min_x) * (max_y - min_y));
+        for y in min_y..max_y {
+            let row = y * self.width;
+            pixels.extend_from_slice(&self.pixels[(row + min_x)..(row + max_x)]);
+        }
+
+        return (max_x - min_x, max_y - min_y, pixels);
+    }
+
+    pub fn show(&mut self, ctx: &egui::Context) -> RegionAction {
+        let mut action = RegionAction::None;
+
+        // The capture of every monitor is usually much larger than the window, so it's scaled down.
+        let scale = (1200.0 / self.width as f32).min(800.0 / self.height as f32).min(1.0);
+        let size = egui::vec2(self.width as f32 * scale, self.height as f32 * scale);
+
+        ctx.show_viewport_immediate(
+            egui::ViewportId::from_hash_of("viewport-region"),
+            egui::ViewportBuilder::default()
+                .with_title("Select the QR code")
+                .with_inner_size(size + egui::vec2(16.0, 64.0)),
+                |ctx, _class| {
+                    egui::CentralPanel::default().show(ctx, |ui| {
+                        if let Some(error) = self.error {
+                            ui.colored_label(egui::Color32::LIGHT_RED, error);
+                        } else {
+                            ui.label("Drag a rectangle around the QR code, or press Escape to cancel.");
+                        }
+
+                        let texture = self.texture.get_or_insert_with(|| {
+                            let image = egui::ColorImage::from_gray([self.width, self.height], self.pixels.as_slice());
+                            return ctx.load_texture("screen-capture", image, egui::TextureOptions::LINEAR);
+                        });
+
+                        let image = egui::Image::new((texture.id(), size)).sense(egui::Sense::drag());
+                        let response = ui.add(image);
+                        if response.drag_started() {
+                            self.drag_start = response.interact_pointer_pos();
+                        }
+
+                        let pointer = ctx.input(|i| i.pointer.interact_pos());
+                        if let (Some(start), Some(pointer)) = (self.drag_start, pointer) {
+                            let selection = egui::Rect::from_two_pos(start, pointer).intersect(response.rect);
+                            let stroke = egui::Stroke::new(2.0, egui::Color32::LIGHT_RED);
+                            ui.painter().rect_stroke(selection, 0.0, stroke);
+
+                            if response.drag_stopped() {
+                                self.drag_start = None;
+
+                                let offset = response.rect.min.to_vec2();
+                                let selection = egui::Rect::from_min_max(
+                                    ((selection.min - offset).to_vec2() / scale).to_pos2(),
+                                    ((selection.max - offset).to_vec2() / scale).to_pos2(),
+                                );
+                                let (width, height, pixels) = self.crop(selection);
+                                if width != 0 && height != 0 {
+                                    action = RegionAction::Select(width, height, pixels);
+                                }
+                            }
+                        }
+                    });
+
+                    if ctx.input(|i| i.viewport().close_requested() || i.key_pressed(egui::Key::Escape)) {
+                        action = RegionAction::Cancel;
+                    }
+                },
+        );
+
+        return action;
+    }
+}
+
+struct SettingsWindow {
+    calibration_row: usize,
+    calibration_code: String,
+    calibration_result: Option<String>,
+}
+
+impl SettingsWindow {
+    pub fn open() -> Self {
+        return Self {
+            calibration_row: 0,
+            calibration_code: String::new(),
+            calibration_result: None,
+        };
+    }
+
+    fn calibrate(&mut self, settings: &mut Settings, rows: &[Row]) {
+        let row = match rows.get(self.calibration_row) {
+            Some(row) => row,
+            None => return,
+        };
+
+        // The offset is searched from the corrected clock, so it's added to the current one.
+        if let Some(offset) = row.secret.find_time_offset(self.calibration_code.as_str()) {
+            settings.time_offset = settings
+                .time_offset
+                .saturating_add(offset)
+                .clamp(-totp::MAX_TIME_OFFSET, totp::MAX_TIME_OFFSET);
+            totp::set_time_offset(settings.time_offset);
+            self.calibration_result = Some(format!("Clock moved by {} seconds", offset));
+        } else {
+            self.calibration_result = Some("This code doesn't match any nearby window".to_string());
+        }
+    }
+
+    /// Returns false once the window is closed, the settings are then saved by the caller.
+    pub fn show(&mut self, ctx: &egui::Context, settings: &mut Settings, rows: &[Row]) -> bool {
+        let mut is_open = true;
+        egui::Window::new("Settings")
+            .open(&mut is_open)
+            .resizable(false)
+            .collapsible(false)
+            .show(ctx, |ui| {
+                egui::Grid::new("settings_grid").num_columns(2).show(ui, |ui| {
+                    ui.label("Time offset:");
+                    let drag = egui::DragValue::new(&mut settings.time_offset)
+                        .clamp_range(-totp::MAX_TIME_OFFSET..=totp::MAX_TIME_OFFSET)
+                        .suffix(" s");
+                    if ui.add(drag).changed() {
+                        totp::set_time_offset(settings.time_offset);
+                    }
+                    ui.end_row();
+
+                    ui.label("Auto-lock after:");
+                    let drag = egui::DragValue::new(&mut settings.auto_lock_minutes)
+                        .clamp_range(0..=1440)
+                        .suffix(" min");
+                    ui.add(drag)
+                        .on_hover_text("Minutes without any input, 0 to never lock the vault");
+                    ui.end_row();
+
+                    ui.label("Clear clipboard after:");
+                    let drag = egui::DragValue::new(&mut settings.clipboard_clear_seconds)
+                        .clamp_range(0..=clipboard::MAX_CLEAR_SECONDS)
+                        .suffix(" s");
+                    ui.add(drag)
+                        .on_hover_text("Seconds before a copied code or secret is cleared, 0 to keep it");
+                    ui.end_row();
+                });
+
+                ui.checkbox(&mut settings.privacy_mode, "Privacy mode")
+                    .on_hover_text("Mask the codes until hovered or clicked, and ask the master password before showing a secret");
+                ui.checkbox(&mut settings.lock_on_minimize, "Lock when minimized");
+                ui.checkbox(&mut settings.lock_on_screen_lock, "Lock when the screen is locked");
+
+                ui.separator();
+                ui.label("Calibrate with a code the server accepted:");
+                ui.add_enabled_ui(!rows.is_empty(), |ui| {
+                    let selected = rows
+                        .get(self.calibration_row)
+                        .map(|row| row.secret.name.as_str())
+                        .unwrap_or("");
+
+                    egui::ComboBox::from_id_source("calibration_row")
+                        .selected_text(selected)
+                        .show_ui(ui, |ui| {
+                            for (idx, row) in rows.iter().enumerate() {
+                                ui.selectable_value(&mut self.calibration_row, idx, row.secret.name.as_str());
+                            }
+                        });
+
+                    ui.horizontal(|ui| {
+                        ui.text_edit_singleline(&mut self.calibration_code);
+                        if ui.button("Calibrate").clicked() {
+                            self.calibrate(settings, rows);
+                        }
+                    });
+                });
+
+                if let Some(result) = &self.calibration_result {
+                    ui.label(result);
+                }
+            });
+
+        return is_open;
     }
 }
 
@@ -127,7 +776,13 @@ fn load_icon() -> Option<egui::IconData> {
     }
 }
 
-pub fn build(path: Option<&str>, password: Option<String>) -> Result<(), eframe::Error> {
+pub fn build(
+    path: Option<&str>,
+    password: Option<Zeroizing<String>>,
+    keyfile: Option<PathBuf>,
+    secrets: Vec<vault::VaultSecret>,
+    settings: Settings,
+) -> Result<(), eframe::Error> {
     let options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default()
             .with_inner_size([320.0, 480.0])
@@ -136,15 +791,17 @@ pub fn build(path: Option<&str>, password: Option<String>) -> Result<(), eframe:
         ..Default::default()
     };
 
-    let mut app = Box::new(App::new(path.map(PathBuf::from)));
+    let open_now = password.is_some() || keyfile.is_some();
+    let mut app = Box::new(App::new(path.map(PathBuf::from), keyfile, secrets, settings));
     return eframe::run_native(
         "Stip",
         options,
-        Box::new(|cc| {
+        Box::new(move |cc| {
             egui_extras::install_image_loaders(&cc.egui_ctx);
 
-            if let Some(password) = password {
-                app.try_open_db(&cc.egui_ctx, password.as_str());
+            if open_now {
+                let keyfile = app.keyfile.clone();
+                app.try_open_db(&cc.egui_ctx, password.as_deref().map(String::as_str), keyfile.as_deref());
             }
 
             return app;
@@ -153,42 +810,329 @@ pub fn build(path: Option<&str>, password: Option<String>) -> Result<(), eframe:
 }
 
 impl App {
-    fn new(path: Option<PathBuf>) -> App {
+    fn new(
+        path: Option<PathBuf>,
+        keyfile: Option<PathBuf>,
+        secrets: Vec<vault::VaultSecret>,
+        settings: Settings,
+    ) -> App {
         let database = path.map(Db::Path).unwrap_or(Db::None);
         let app = Self {
+            keyfile,
             password_modal: None,
+            new_vault_modal: None,
+            add_secret_modal: None,
+            import_modal: None,
+            settings_window: None,
+            region_window: None,
             dropped_files: Vec::new(),
+            settings,
             database,
-            rows: Vec::new(),
+            rows: secrets.into_iter().map(Row::new).collect(),
+            collapsed_groups: HashSet::new(),
+            new_group: String::new(),
+            filter: String::new(),
+            focus_filter: true,
             icon_textures: Vec::new(),
+            unsaved_changes: false,
+            last_activity: Instant::now(),
+            last_screen_lock_check: Instant::now(),
+            was_minimized: false,
+            screen_was_locked: false,
+            copied: None,
+            notifications: Vec::new(),
         };
 
         return app;
     }
 
-    fn try_open_db(&mut self, ctx: &egui::Context, password: &str) -> bool {
+    fn try_open_db(&mut self, ctx: &egui::Context, password: Option<&str>, keyfile: Option<&Path>) -> bool {
         if let Db::Path(path) = self.database.take() {
-            if let Ok(vault) = vault::Vault::open(path.clone(), password) {
-                self.rows = vault.secrets().into_iter().map(Row::new).collect::<Vec<Row>>();
-                for icon in vault.custom_icons.iter() {
-                    Self::add_texture_from_image(&mut self.icon_textures, ctx, icon);
+            match vault::Vault::open(path.clone(), password, keyfile) {
+                Ok(vault) => {
+                    let (secrets, errors) = vault.secrets();
+                    for err in errors {
+                        self.notify("Skipped an entry", &err);
+                    }
+
+                    self.rows = secrets.into_iter().map(Row::new).collect::<Vec<Row>>();
+                    self.unsaved_changes = false;
+                    for icon in vault.custom_icons.iter() {
+                        Self::add_texture_from_image(&mut self.icon_textures, ctx, icon);
+                    }
+                    self.database = Db::Opened(Box::new(vault));
+                    return true;
+                }
+                Err(err) => {
+                    self.notify("Failed to open the vault", &err);
+                    self.database = Db::Path(path);
+                    return false;
                 }
-                self.database = Db::Opened;
-                return true;
-            } else {
-                self.database = Db::Path(path);
-                return false;
             }
         } else {
             return false;
         }
     }
 
+    /// Period shared by every TOTP row, if any. When the periods differ, a single progress bar
+    /// would be wrong for some rows, so only the countdown of each row is shown.
+    fn common_period(&self) -> Option<u64> {
+        let mut periods = self
+            .rows
+            .iter()
+            .filter(|row| row.secret.kind() == OtpKind::Totp)
+            .map(|row| row.secret.period());
+
+        let period = periods.next().unwrap_or(30);
+        if periods.all(|other| other == period) {
+            return Some(period);
+        } else {
+            return None;
+        }
+    }
+
+    /// Add the secrets read from the QR codes of an image. A single secret is added right away,
+    /// otherwise they are listed so the user confirms which ones to add.
+    fn import_secrets(&mut self, results: Vec<Result<vault::VaultSecret, vault::Error>>) {
+        if results.is_empty() {
+            self.notify("Failed to add a secret", &vault::Error::NoQrCode);
+            return;
+        }
+
+        if results.len() == 1 {
+            match results.into_iter().next() {
+                Some(Ok(secret)) => self.add_row(secret),
+                Some(Err(err)) => self.notify("Failed to add the secret", &err),
+                None => {}
+            }
+            return;
+        }
+
+        self.import_modal = Some(ImportWindow::open(results));
+    }
+
+    fn add_row(&mut self, secret: vault::VaultSecret) {
+        self.rows.push(Row::new(secret));
+        if let Db::Opened(_) = self.database {
+            self.unsaved_changes = true;
+        }
+    }
+
+    /// Write every row to the opened database, adding entries for the rows that don't have one.
+    fn save_db(&mut self) {
+        let mut errors = Vec::new();
+        if let Db::Opened(vault) = &mut self.database {
+            for row in self.rows.iter_mut() {
+                if row.secret.entry().is_some() {
+                    if let Err(err) = vault.update_secret(&mut row.secret) {
+                        errors.push(err);
+                    }
+                } else {
+                    vault.add_secret(&mut row.secret);
+                }
+            }
+
+            match vault.save() {
+                Ok(()) => self.unsaved_changes = false,
+                Err(err) => errors.push(err),
+            }
+        }
+
+        for err in errors {
+            self.notify("Failed to save the vault", &err);
+        }
+    }
+
+    fn notify(&mut self, context: &str, err: &vault::Error) {
+        self.notify_message(format!("{}, {}", context, err));
+    }
+
+    fn notify_message(&mut self, message: String) {
+        eprintln!("{}", message);
+        self.notifications.push(Notification {
+            message,
+            shown_until: Instant::now() + NOTIFICATION_DURATION,
+        });
+    }
+
+    fn draw_notifications(&mut self, ctx: &egui::Context) {
+        let now = Instant::now();
+        self.notifications.retain(|notification| now < notification.shown_until);
+        if self.notifications.is_empty() {
+            return;
+        }
+
+        let mut dismissed = None;
+        egui::Area::new(egui::Id::new("notifications"))
+            .anchor(egui::Align2::CENTER_BOTTOM, egui::vec2(0.0, -8.0))
+            .order(egui::Order::Foreground)
+            .show(ctx, |ui| {
+                ui.set_max_width(ctx.screen_rect().width() - 16.0);
+                for (idx, notification) in self.notifications.iter().enumerate() {
+                    egui::Frame::popup(ui.style()).show(ui, |ui| {
+                        ui.horizontal(|ui| {
+                            if ui.small_button("✖").on_hover_text("Dismiss").clicked() {
+                                dismissed = Some(idx);
+                            }
+
+                            let text = egui::RichText::new(notification.message.as_str())
+                                .color(egui::Color32::LIGHT_RED);
+                            ui.add(egui::Label::new(text).wrap(true));
+                        });
+                    });
+                }
+            });
+
+        if let Some(idx) = dismissed {
+            self.notifications.remove(idx);
+        }
+    }
+
+    /// Forget the decrypted database and every secret, so the password is asked again. Unsaved
+    /// changes are saved first, and the vault stays open if they can't be, otherwise they would
+    /// be lost. Returns whether the vault is locked.
+    fn lock(&mut self, ctx: &egui::Context) -> bool {
+        if !matches!(self.database, Db::Opened(_)) {
+            return false;
+        }
+
+        if self.unsaved_changes {
+            self.save_db();
+            if self.unsaved_changes {
+                self.notify_message("The vault wasn't locked, as its changes couldn't be saved".to_string());
+                return false;
+            }
+        }
+
+        if let Db::Opened(vault) = self.database.take() {
+            self.database = Db::Path(vault.path.clone());
+        }
+
+        self.rows.clear();
+        self.icon_textures.clear();
+        self.filter.clear();
+        self.add_secret_modal = None;
+        self.import_modal = None;
+        self.region_window = None;
+        password::forget_fields(ctx);
+        self.clear_copied_text();
+        return true;
+    }
+
+    /// Lock the vault after the idle timeout, or when the window is minimized or the screen is
+    /// locked, as configured in the settings.
+    fn check_auto_lock(&mut self, ctx: &egui::Context) {
+        let now = Instant::now();
+        if ctx.input(|i| !i.events.is_empty()) {
+            self.last_activity = now;
+        }
+
+        if !matches!(self.database, Db::Opened(_)) {
+            return;
+        }
+
+        let timeout = Duration::from_secs(self.settings.auto_lock_minutes.saturating_mul(60));
+        let idle = self.settings.auto_lock_minutes != 0 && timeout <= now.duration_since(self.last_activity);
+
+        let minimized = ctx.input(|i| i.viewport().minimized.unwrap_or(false));
+        let just_minimized = self.settings.lock_on_minimize && minimized && !self.was_minimized;
+        self.was_minimized = minimized;
+
+        let mut just_screen_locked = false;
+        if self.settings.lock_on_screen_lock
+            && SCREEN_LOCK_CHECK_INTERVAL <= now.duration_since(self.last_screen_lock_check)
+        {
+            self.last_screen_lock_check = now;
+            let screen_locked = sys::is_screen_locked();
+            just_screen_locked = screen_locked && !self.screen_was_locked;
+            self.screen_was_locked = screen_locked;
+        }
+
+        if (idle || just_minimized || just_screen_locked) && !self.lock(ctx) {
+            // Wait for another timeout before trying again.
+            self.last_activity = now;
+        }
+    }
+
+    /// Copy the text the widgets asked to copy ourselves, so it can be marked as sensitive and
+    /// cleared after the delay of the settings.
+    fn handle_copied_text(&mut self, ctx: &egui::Context) {
+        let text = Zeroizing::new(ctx.output_mut(|o| std::mem::take(&mut o.copied_text)));
+        if !text.is_empty() {
+            let clear_after = match self.settings.clipboard_clear_seconds {
+                0 => None,
+                seconds => Some(Duration::from_secs(seconds)),
+            };
+
+            match clipboard::CopiedText::copy(text.as_str(), clear_after) {
+                Ok(copied) => self.copied = Some(copied),
+                // Let egui copy it, at least the copy button still works.
+                Err(err) => {
+                    self.notify("The copy won't be cleared from the clipboard", &err);
+                    ctx.output_mut(|o| o.copied_text = text.to_string());
+                }
+            }
+        }
+
+        match self.copied.as_ref().and_then(clipboard::CopiedText::remaining) {
+            Some(remaining) if remaining.is_zero() => self.clear_copied_text(),
+            // Nothing may be repainted until then, e.g. once the vault is locked.
+            Some(remaining) => ctx.request_repaint_after(remaining),
+            None => {}
+        }
+    }
+
+    fn clear_copied_text(&mut self) {
+        if let Some(copied) = self.copied.take() {
+            copied.clear();
+        }
+    }
+
     fn pick_database() -> Option<PathBuf> {
         let file_dialog = FileDialog::new();
         return file_dialog.pick_file();
     }
 
+    fn pick_new_database() -> Option<PathBuf> {
+        let file_dialog = FileDialog::new()
+            .add_filter("KeePass database", &["kdbx"])
+            .set_file_name("stip.kdbx");
+        return file_dialog.save_file();
+    }
+
+    /// Create the vault and store the rows already shown in it, for instance the secrets that
+    /// were dropped in the window before any vault was opened.
+    fn create_db(&mut self, path: PathBuf, password: &str) -> bool {
+        // The changes of the opened vault are written to it first, rather than lost.
+        if matches!(self.database, Db::Opened(_)) && self.unsaved_changes {
+            self.save_db();
+            if self.unsaved_changes {
+                self.notify_message("The opened vault has changes that couldn't be saved".to_string());
+                return false;
+            }
+        }
+
+        match vault::Vault::create(path, password) {
+            Ok(vault) => {
+                // Rows read from a previously opened vault stay in that vault.
+                self.rows.retain(|row| row.secret.entry().is_none());
+                self.icon_textures.clear();
+                // The new vault is only protected by its password.
+                self.keyfile = None;
+                self.database = Db::Opened(Box::new(vault));
+                self.unsaved_changes = !self.rows.is_empty();
+                if self.unsaved_changes {
+                    self.save_db();
+                }
+                return true;
+            }
+            Err(err) => {
+                self.notify("Failed to create the vault", &err);
+                return false;
+            }
+        }
+    }
+
     fn add_texture_from_image(
         icon_textures: &mut Vec<egui::TextureHandle>,
         ctx: &egui::Context,
@@ -209,6 +1153,13 @@ impl App {
 
         menu::bar(ui, |ui| {
             ui.menu_button("File", |ui| {
+                if ui.button("New vault").clicked() {
+                    ui.close_menu();
+                    if let Some(path) = Self::pick_new_database() {
+                        self.new_vault_modal = Some(NewVaultWindow::open(path));
+                    }
+                }
+
                 if ui.button("Open").clicked() {
                     ui.close_menu();
                     if let Some(path) = Self::pick_database() {
@@ -217,38 +1168,61 @@ impl App {
                         self.database = Db::Path(path);
                     }
                 }
+
+                let can_save = self.unsaved_changes && matches!(self.database, Db::Opened(_));
+                let save_button = egui::Button::new("Save").shortcut_text("Ctrl+S");
+                if ui.add_enabled(can_save, save_button).clicked() {
+                    ui.close_menu();
+                    self.save_db();
+                }
+
+                let lock_button = egui::Button::new("Lock").shortcut_text("Ctrl+L");
+                if ui.add_enabled(matches!(self.database, Db::Opened(_)), lock_button).clicked() {
+                    ui.close_menu();
+                    self.lock(ui.ctx());
+                }
+
+                if ui.button("Settings").clicked() {
+                    ui.close_menu();
+                    self.settings_window = Some(SettingsWindow::open());
+                }
             });
 
             ui.with_layout(egui::Layout::right_to_left(egui::Align::Min), |ui| {
                 let img = egui::include_image!("../assets/plus.svg");
                 ui.menu_image_button(img, |ui| {
-                    /*
-                    if ui.button("Capture").clicked() {
+                    if ui.button("Select in screen").clicked() {
+                        match sys::capture_screen() {
+                            Ok((width, height, data)) => {
+                                self.region_window = Some(RegionWindow::open(width, height, data));
+                            }
+                            Err(err) => self.notify("Failed to select in the screen", &vault::Error::ScreenCapture(err)),
+                        }
                         ui.close_menu();
                     }
-                    */
 
                     if ui.button("Detect in screen").clicked() {
-                        if let Ok((width, height, data)) = sys::capture_screen() {
-                            match vault::VaultSecret::from_slice(width, height, data.as_slice()) {
-                                Ok(secret) => self.rows.push(Row::new(secret)),
-                                Err(err) => eprintln!("Failed to add the secret detected in the screen, err: {:?}", err),
+                        match sys::capture_screen() {
+                            Ok((width, height, data)) => {
+                                self.import_secrets(vault::VaultSecret::all_from_slice(width, height, data.as_slice()));
                             }
-                        } else {
-                            eprintln!("Couldn't detect any secret in the screen")
+                            Err(err) => self.notify("Failed to detect in the screen", &vault::Error::ScreenCapture(err)),
                         }
                         ui.close_menu();
                     }
 
-                    /*
                     if ui.button("Add from clipboard").clicked() {
+                        match clipboard::read_secret() {
+                            Ok(secret) => self.add_row(secret),
+                            Err(err) => self.notify("Failed to add the secret from the clipboard", &err),
+                        }
                         ui.close_menu();
                     }
 
                     if ui.button("Add manually").clicked() {
+                        self.add_secret_modal = Some(AddSecretWindow::open());
                         ui.close_menu();
                     }
-                    */
 
                     if ui.button("Close the menu").clicked() {
                         ui.close_menu();
@@ -258,81 +1232,309 @@ impl App {
         });
     }
 
+    /// Groups of the rows, sorted so that nested groups follow their parent. The root group,
+    /// an empty path, is always first.
+    fn groups(&self) -> Vec<Vec<vault::VaultGroup>> {
+        let mut groups = self.rows.iter().map(|row| row.secret.group.clone()).collect::<Vec<Vec<vault::VaultGroup>>>();
+        groups.push(Vec::new());
+        groups.sort();
+        groups.dedup();
+        return groups;
+    }
+
+    /// First row matching the filter, in the order of the grid.
+    fn first_match(&self) -> Option<usize> {
+        return self.groups().iter().find_map(|group| {
+            return self.rows.iter().position(|row| {
+                return row.secret.group == *group && row.secret.matches(self.filter.as_str());
+            });
+        });
+    }
+
+    fn draw_filter(&mut self, ui: &mut egui::Ui) {
+        let text_edit = egui::TextEdit::singleline(&mut self.filter)
+            .hint_text("Search (Ctrl+F)")
+            .desired_width(f32::INFINITY);
+        let response = ui.add(text_edit);
+
+        // The focus is only taken once the modal windows are closed, so they keep it until then.
+        if self.focus_filter && ui.is_enabled() {
+            response.request_focus();
+            self.focus_filter = false;
+        }
+
+        if response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+            if let Some(idx) = self.first_match() {
+                let code = self.rows[idx].secret.code();
+                ui.output_mut(|o| o.copied_text = code);
+            }
+        }
+    }
+
     fn draw_grid_content(&mut self, ctx: &egui::Context, ui: &mut egui::Ui) {
-        let first_column_size = [175.0, ui.available_height()];
-        for (idx, row) in self.rows.iter_mut().enumerate() {
-            if row.editing {
-                let text_edit = egui::TextEdit::singleline(&mut row.secret.name)
-                    .vertical_align(egui::Align::Center)
-                    .horizontal_align(egui::Align::Center);
-
-                if ui.add_sized(first_column_size, text_edit).lost_focus() {
-                    row.editing = false;
-                    if ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+        let groups = self.groups();
+        let mut updated_rows = Vec::new();
+        let mut moved_rows = Vec::new();
+        let filter = self.filter.clone();
+        for group in groups.iter() {
+            let visible = |row: &Row| row.secret.group == *group && row.secret.matches(filter.as_str());
+            if !self.rows.iter().any(visible) {
+                continue;
+            }
+
+            if !group.is_empty() {
+                // Groups are expanded while filtering, so the matches are never hidden.
+                let collapsed = self.filter.is_empty() && self.collapsed_groups.contains(group);
+                let arrow = if collapsed { "⏵" } else { "⏷" };
+                let header = egui::RichText::new(format!("{} {}", arrow, group_label(group))).strong();
+                if ui.add(egui::Label::new(header).sense(egui::Sense::click())).clicked() {
+                    if collapsed {
+                        self.collapsed_groups.remove(group);
+                    } else {
+                        self.collapsed_groups.insert(group.clone());
                     }
                 }
-            } else {
-                let label = egui::Label::new(&row.secret.name).truncate(true);
-                let response = ui.add_sized(first_column_size, label);
-                if response.double_clicked() {
-                    row.editing = true;
+                ui.end_row();
+
+                if collapsed {
+                    continue;
                 }
+            }
 
-                response.context_menu(|ui| {
-                    if ui.button("Show details").clicked() {
-                        row.show_details = true;
-                        ui.close_menu();
-                    }
+            for idx in 0..self.rows.len() {
+                if !visible(&self.rows[idx]) {
+                    continue;
+                }
 
-                    if ui.button("Close the menu").clicked() {
-                        ui.close_menu();
-                    }
-                });
+                if self.draw_row(ctx, ui, idx, groups.as_slice(), &mut moved_rows) {
+                    updated_rows.push(idx);
+                }
             }
+        }
 
-            if let Some(icon_idx) = row.secret.icon.clone() {
-                let texture = &self.icon_textures[icon_idx];
-                ui.image((texture.id(), egui::vec2(texture.aspect_ratio() * ICON_DIM, ICON_DIM)));
-            } else {
-                ui.label("");
+        // Rows are moved once drawn, so a row moved to a later group isn't drawn twice.
+        for (idx, group) in moved_rows {
+            self.rows[idx].secret.group = group;
+            self.unsaved_changes = true;
+        }
+
+        // HOTP counters must survive restarts, so they are written back to the database as soon
+        // as they are incremented.
+        let mut errors = Vec::new();
+        if let Db::Opened(vault) = &mut self.database {
+            for idx in updated_rows {
+                let secret = &mut self.rows[idx].secret;
+                if secret.entry().is_none() {
+                    continue;
+                }
+
+                if let Err(err) = vault.update_secret(secret).and_then(|_| vault.save()) {
+                    errors.push(err);
+                }
             }
+        }
 
-            let token = totp::from_now_with_period(
-                row.secret.secret(),
-                row.secret.period(),
-                row.secret.digits(),
-            );
+        for err in errors {
+            self.notify("Failed to save the counter", &err);
+        }
+    }
+
+    /// Draw the row `idx` of the grid, returns true if the HOTP counter of the row was
+    /// incremented.
+    fn draw_row(
+        &mut self,
+        ctx: &egui::Context,
+        ui: &mut egui::Ui,
+        idx: usize,
+        groups: &[Vec<vault::VaultGroup>],
+        moved_rows: &mut Vec<(usize, Vec<vault::VaultGroup>)>,
+    ) -> bool {
+        let first_column_size = [175.0, ui.available_height()];
+        let mut counter_incremented = false;
+        let privacy_mode = self.settings.privacy_mode;
+        let vault_opened = matches!(self.database, Db::Opened(_));
+        let row = &mut self.rows[idx];
+        if row.editing {
+            let text_edit = egui::TextEdit::singleline(&mut row.secret.name)
+                .vertical_align(egui::Align::Center)
+                .horizontal_align(egui::Align::Center);
+
+            let response = ui.add_sized(first_column_size, text_edit);
+            if response.changed() {
+                self.unsaved_changes = true;
+            }
+
+            if response.lost_focus() {
+                row.editing = false;
+            }
+        } else {
+            let label = egui::Label::new(&row.secret.name).truncate(true);
+            let response = ui.add_sized(first_column_size, label);
+            if response.double_clicked() {
+                row.editing = true;
+            }
+
+            response.context_menu(|ui| {
+                if ui.button("Show details").clicked() {
+                    row.show_details = true;
+                    ui.close_menu();
+                }
+
+                ui.menu_button("Move to group", |ui| {
+                    for group in groups.iter().filter(|group| **group != row.secret.group) {
+                        if ui.button(group_label(group)).clicked() {
+                            moved_rows.push((idx, group.clone()));
+                            ui.close_menu();
+                        }
+                    }
+
+                    ui.separator();
+                    ui.horizontal(|ui| {
+                        let text_edit = egui::TextEdit::singleline(&mut self.new_group)
+                            .hint_text("New group, e.g. Work/AWS")
+                            .desired_width(160.0);
+                        ui.add(text_edit);
+
+                        let group = self
+                            .new_group
+                            .split('/')
+                            .map(str::trim)
+                            .filter(|name| !name.is_empty())
+                            .map(vault::VaultGroup::named)
+                            .collect::<Vec<vault::VaultGroup>>();
+                        if ui.add_enabled(!group.is_empty(), egui::Button::new("Move")).clicked() {
+                            moved_rows.push((idx, group));
+                            self.new_group.clear();
+                            ui.close_menu();
+                        }
+                    });
+                });
 
-            let token_text = format!("{:0digits$}", token.number, digits = row.secret.digits());
-            ui.label(&token_text);
+                if ui.button("Close the menu").clicked() {
+                    ui.close_menu();
+                }
+            });
+        }
 
-            let img = egui::Image::new(egui::include_image!("../assets/copy.svg"));
-            let button = egui::ImageButton::new(img);
-            if ui.add_sized([ICON_DIM, ICON_DIM], button).clicked() {
+        if let Some(icon_idx) = row.secret.icon.clone() {
+            let texture = &self.icon_textures[icon_idx];
+            ui.image((texture.id(), egui::vec2(texture.aspect_ratio() * ICON_DIM, ICON_DIM)));
+        } else {
+            ui.label("");
+        }
+
+        let (token_text, next_token_text) = row.secret.codes();
+        if row.secret.kind() == OtpKind::Hotp {
+            ui.horizontal(|ui| {
+                row.draw_code(ui, &token_text, privacy_mode);
+                if ui.small_button("⟳").on_hover_text("Next code").clicked() {
+                    row.secret.increment_counter();
+                    counter_incremented = true;
+                }
+            });
+        } else {
+            ui.horizontal(|ui| {
+                row.draw_code(ui, &token_text, privacy_mode);
+
+                let remaining = row.secret.remaining().unwrap_or_default();
+                let next_token_text = next_token_text
+                    .as_ref()
+                    .filter(|_| remaining <= SHOW_NEXT_CODE_BEFORE);
+                if let Some(next_token_text) = next_token_text {
+                    let text = if privacy_mode && !row.show_code && !row.code_hovered {
+                        mask_code(next_token_text)
+                    } else {
+                        next_token_text.clone()
+                    };
+
+                    let label = egui::Label::new(egui::RichText::new(text).weak())
+                        .sense(egui::Sense::click());
+                    if ui.add(label).on_hover_text("Next code, click to copy").clicked() {
+                        ui.output_mut(|o| o.copied_text = next_token_text.clone());
+                    }
+                }
+
+                let seconds = remaining.as_secs_f32().ceil() as u64;
+                ui.weak(format!("{}s", seconds))
+                    .on_hover_text(format!("Expires in {} seconds", seconds));
+            });
+        }
+
+        let img = egui::Image::new(egui::include_image!("../assets/copy.svg"));
+        let button = egui::ImageButton::new(img);
+        let response = ui.add_sized([ICON_DIM, ICON_DIM], button);
+        if response.clicked() {
+            ui.output_mut(|o| o.copied_text = token_text.clone());
+        }
+
+        response.context_menu(|ui| {
+            if ui.button("Copy current code").clicked() {
                 ui.output_mut(|o| o.copied_text = token_text.clone());
+                ui.close_menu();
             }
 
-            ui.end_row();
+            if let Some(next_token_text) = &next_token_text {
+                if ui.button("Copy next code").clicked() {
+                    ui.output_mut(|o| o.copied_text = next_token_text.clone());
+                    ui.close_menu();
+                }
+            }
+        });
 
+        ui.end_row();
 
-            if row.show_details {
-                row.draw_details_window(idx, ctx);
+        if row.show_details {
+            row.draw_details_window(idx, ctx, privacy_mode, vault_opened);
+        }
+
+        if let Some(prompt) = row.reveal_prompt.as_mut().filter(|prompt| prompt.submitted) {
+            // An empty password with a key file means that the database is protected by the key
+            // file only, as when opening it.
+            let password = Some(prompt.password.as_str())
+                .filter(|password| !password.is_empty() || self.keyfile.is_none());
+            let accepted = match &self.database {
+                Db::Opened(vault) => vault.check_key(password, self.keyfile.as_deref()),
+                _ => false,
+            };
+
+            if accepted {
+                row.revealed = true;
+                row.reveal_prompt = None;
+                password::forget_fields(ctx);
+            } else {
+                prompt.failed();
             }
         }
+
+        return counter_incremented;
+    }
+}
+
+fn group_label(group: &[vault::VaultGroup]) -> String {
+    if group.is_empty() {
+        return "(no group)".to_string();
+    } else {
+        return group.iter().map(|group| group.name.as_str()).collect::<Vec<&str>>().join("/");
     }
 }
 
 impl eframe::App for App {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        // Checked first, so the secrets are never drawn once the vault should be locked.
+        self.check_auto_lock(ctx);
+
         if self.password_modal.is_none() {
             if let Db::Path(_) = self.database {
-                self.password_modal = Some(PasswordWindow::open());
+                self.password_modal = Some(PasswordWindow::open(self.keyfile.clone()));
             }
         }
 
         if let Some(mut window) = self.password_modal.take() {
-            if let Some(password) = window.show(ctx) {
-                if !self.try_open_db(ctx, password.as_ref()) {
+            if window.show(ctx) {
+                if self.try_open_db(ctx, window.password(), window.keyfile.as_deref()) {
+                    self.keyfile = window.keyfile;
+                    password::forget_fields(ctx);
+                } else {
                     self.password_modal = Some(window.failed());
                 }
             } else {
@@ -340,19 +1542,102 @@ impl eframe::App for App {
             }
         }
 
+        if ctx.input_mut(|i| i.consume_key(egui::Modifiers::COMMAND, egui::Key::S)) {
+            self.save_db();
+        }
+
+        if ctx.input_mut(|i| i.consume_key(egui::Modifiers::COMMAND, egui::Key::L)) {
+            self.lock(ctx);
+        }
+
+        if let Some(mut window) = self.new_vault_modal.take() {
+            match window.show(ctx) {
+                NewVaultAction::Create => {
+                    if self.create_db(window.path.clone(), window.password.as_str()) {
+                        password::forget_fields(ctx);
+                    } else {
+                        window.error = Some("Failed to create the vault");
+                        self.new_vault_modal = Some(window);
+                    }
+                }
+                NewVaultAction::Cancel => password::forget_fields(ctx),
+                NewVaultAction::None => self.new_vault_modal = Some(window),
+            }
+        }
+
+        if let Some(mut window) = self.add_secret_modal.take() {
+            match window.show(ctx) {
+                AddSecretAction::Add(parsed_url) => {
+                    self.add_row(vault::VaultSecret::from_parsed_url(parsed_url));
+                    password::forget_fields(ctx);
+                }
+                AddSecretAction::Cancel => password::forget_fields(ctx),
+                AddSecretAction::None => self.add_secret_modal = Some(window),
+            }
+        }
+
+        if let Some(mut window) = self.region_window.take() {
+            match window.show(ctx) {
+                RegionAction::Select(width, height, data) => {
+                    let results = vault::VaultSecret::all_from_slice(width, height, data.as_slice());
+                    if results.iter().any(Result::is_ok) {
+                        self.import_secrets(results);
+                    } else {
+                        window.error = Some("No QR code was found in the selection");
+                        self.region_window = Some(window);
+                    }
+                }
+                RegionAction::Cancel => (),
+                RegionAction::None => self.region_window = Some(window),
+            }
+        }
+
+        if let Some(mut window) = self.import_modal.take() {
+            match window.show(ctx) {
+                ImportAction::Add(secrets) => {
+                    for secret in secrets {
+                        self.add_row(secret);
+                    }
+                }
+                ImportAction::Cancel => (),
+                ImportAction::None => self.import_modal = Some(window),
+            }
+        }
+
+        if let Some(mut window) = self.settings_window.take() {
+            if window.show(ctx, &mut self.settings, self.rows.as_slice()) {
+                self.settings_window = Some(window);
+            } else if let Err(err) = self.settings.save() {
+                self.notify_message(format!("Failed to save the settings, {}", err));
+            }
+        }
+
         egui::CentralPanel::default().show(ctx, |ui| {
-            ui.add_enabled_ui(self.password_modal.is_none(), |ui| {
+            let modal_shown = self.password_modal.is_some()
+                || self.new_vault_modal.is_some()
+                || self.add_secret_modal.is_some()
+                || self.import_modal.is_some();
+            ui.add_enabled_ui(!modal_shown, |ui| {
                 self.show_menu(ctx, ui);
                 ui.separator();
 
                 // We call `request_repaint` otherwise the progress bar glitch, presumably, because
                 // it doesn't know it has to repaint every frame?
                 ui.ctx().request_repaint();
-                ui.add(egui::widgets::ProgressBar::new(totp::progress()));
-                ui.separator();
+                if let Some(period) = self.common_period() {
+                    ui.add(egui::widgets::ProgressBar::new(totp::progress(period)));
+                    ui.separator();
+                }
 
                 ui.set_visible(true);
 
+                if ui.input_mut(|i| i.consume_key(egui::Modifiers::COMMAND, egui::Key::F)) {
+                    self.focus_filter = true;
+                }
+
+                self.draw_filter(ui);
+                ui.separator();
+
                 egui::ScrollArea::vertical().show(ui, |ui| {
                     egui::Grid::new("my_grid")
                         .num_columns(4)
@@ -368,16 +1653,28 @@ impl eframe::App for App {
             }
         });
 
-        for file in self.dropped_files.iter() {
+        // Files that couldn't be read aren't QR codes, so they are reported on their own.
+        let mut results = Vec::new();
+        for file in std::mem::take(&mut self.dropped_files) {
             if let Some(path) = file.path.as_deref() {
-                match vault::VaultSecret::from_path(path) {
-                    Ok(secret) => self.rows.push(Row::new(secret)),
-                    Err(err) => eprintln!("Failed to load {:?} as secret, err: {:?}", path, err),
+                match vault::VaultSecret::all_from_path(path, None) {
+                    Ok(secrets) if secrets.is_empty() => self.notify("Failed to read a dropped file", &vault::Error::NoQrCode),
+                    Ok(secrets) => results.extend(secrets),
+                    Err(err) => self.notify("Failed to read a dropped file", &err),
                 }
             }
         }
 
-        self.dropped_files.clear();
+        if !results.is_empty() {
+            self.import_secrets(results);
+        }
+
+        self.handle_copied_text(ctx);
+        self.draw_notifications(ctx);
+    }
+
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        self.clear_copied_text();
     }
 }
 
@@ -400,7 +1697,14 @@ impl<'a> egui::widgets::text_edit::TextBuffer for FakeMutableStr<'a> {
 }
 
 impl Row {
-    fn draw_details_window_central_panel(&mut self, _ctx: &egui::Context, ui: &mut egui::Ui) {
+    fn draw_details_window_central_panel(
+        &mut self,
+        _ctx: &egui::Context,
+        ui: &mut egui::Ui,
+        privacy_mode: bool,
+        vault_opened: bool,
+    ) {
+        let hidden = privacy_mode && !self.revealed;
         egui::Grid::new("my_grid").num_columns(2).show(ui, |ui| {
             let cursor_height = ui.cursor().height();
 
@@ -412,9 +1716,10 @@ impl Row {
                     ui.output_mut(|o| o.copied_text = self.secret.url().to_string());
                 }
 
+                let url = if hidden { SECRET_MASK } else { self.secret.url() };
                 return ui.add_sized(
                     [ui.available_width(), cursor_height],
-                    egui::TextEdit::singleline(&mut FakeMutableStr(self.secret.url())),
+                    egui::TextEdit::singleline(&mut FakeMutableStr(url)),
                 );
             });
 
@@ -436,17 +1741,18 @@ impl Row {
             ui.end_row();
             ui.label("secret:");
             ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
-                let secret = b32encode(self.secret.secret());
+                let secret = Zeroizing::new(b32encode(self.secret.secret()));
 
                 let img = egui::include_image!("../assets/copy.svg");
                 let button = egui::ImageButton::new(egui::Image::new(img));
                 if ui.add_sized([ICON_DIM, ICON_DIM], button).clicked() {
-                    ui.output_mut(|o| o.copied_text = secret.clone());
+                    ui.output_mut(|o| o.copied_text = secret.to_string());
                 }
 
+                let secret = if hidden { SECRET_MASK } else { secret.as_str() };
                 ui.add_sized(
                     [ui.available_width(), cursor_height],
-                    egui::TextEdit::singleline(&mut FakeMutableStr(secret.as_str())),
+                    egui::TextEdit::singleline(&mut FakeMutableStr(secret)),
                 );
             });
 
@@ -483,10 +1789,77 @@ impl Row {
                     egui::TextEdit::singleline(&mut FakeMutableStr(digit.as_str())),
                 );
             });
+
+            ui.end_row();
+            ui.label("algorithm:");
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                let algorithm = self.secret.algorithm().name();
+
+                let img = egui::include_image!("../assets/copy.svg");
+                let button = egui::ImageButton::new(egui::Image::new(img));
+                if ui.add_sized([ICON_DIM, ICON_DIM], button).clicked() {
+                    ui.output_mut(|o| o.copied_text = algorithm.to_string());
+                }
+
+                return ui.add_sized(
+                    [ui.available_width(), cursor_height],
+                    egui::TextEdit::singleline(&mut FakeMutableStr(algorithm)),
+                );
+            });
         });
+
+        if privacy_mode {
+            ui.separator();
+            self.draw_reveal(ui, vault_opened);
+        }
     }
 
-    fn draw_details_window(&mut self, idx: usize, ctx: &egui::Context) {
+    /// Buttons to reveal and hide the URL and the secret in privacy mode. The master password is
+    /// checked by the caller once it's submitted. Without an opened vault, there is no master
+    /// password to ask, so the secret is revealed right away.
+    fn draw_reveal(&mut self, ui: &mut egui::Ui, vault_opened: bool) {
+        if self.revealed {
+            if ui.button("Hide the secret").clicked() {
+                self.revealed = false;
+            }
+        } else if let Some(prompt) = &mut self.reveal_prompt {
+            let mut cancel = false;
+            ui.horizontal(|ui| {
+                ui.label("Master password:");
+                let response = ui.add(password::password(&mut prompt.password, prompt.failure));
+                if prompt.select {
+                    ui.memory_mut(|mem| mem.request_focus(response.id));
+                    prompt.select = false;
+                }
+
+                if response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                    prompt.submitted = true;
+                }
+
+                if ui.button("Reveal").clicked() {
+                    prompt.submitted = true;
+                }
+
+                if ui.button("Cancel").clicked() {
+                    cancel = true;
+                }
+            });
+
+            if cancel {
+                self.reveal_prompt = None;
+                password::forget_fields(ui.ctx());
+            }
+        } else if !vault_opened {
+            let button = ui.button("Reveal the secret").on_hover_text("No vault is opened, so no password is asked");
+            if button.clicked() {
+                self.revealed = true;
+            }
+        } else if ui.button("Reveal the secret").clicked() {
+            self.reveal_prompt = Some(RevealPrompt::new());
+        }
+    }
+
+    fn draw_details_window(&mut self, idx: usize, ctx: &egui::Context, privacy_mode: bool, vault_opened: bool) {
         ctx.show_viewport_immediate(
             egui::ViewportId::from_hash_of(format!("viewport-details:{}", idx)),
             egui::ViewportBuilder::default()
@@ -494,12 +1867,16 @@ impl Row {
                 .with_inner_size([500.0, 400.0]),
                 |ctx, _class| {
                     egui::CentralPanel::default().show(ctx, |ui| {
-                        self.draw_details_window_central_panel(ctx, ui);
+                        self.draw_details_window_central_panel(ctx, ui, privacy_mode, vault_opened);
                     });
 
                     if ctx.input(|i| i.viewport().close_requested()) {
                         // Tell parent viewport that we should not show next frame:
                         self.show_details = false;
+                        self.revealed = false;
+                        if self.reveal_prompt.take().is_some() {
+                            password::forget_fields(ctx);
+                        }
                     }
                 },
         );
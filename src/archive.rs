@@ -0,0 +1,156 @@
+#![allow(clippy::needless_return)]
+
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use zip::{result::ZipError, ZipArchive};
+
+#[derive(Debug)]
+pub enum Error {
+    Io(std::io::Error),
+    Zip(ZipError),
+    NotFound,
+    InvalidPassword,
+    /// The file is encrypted, but no password was given.
+    PasswordRequired,
+}
+
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        return Error::Io(err);
+    }
+}
+
+impl From<ZipError> for Error {
+    fn from(err: ZipError) -> Self {
+        return match err {
+            ZipError::FileNotFound => Error::NotFound,
+            ZipError::Io(err) => Error::Io(err),
+            ZipError::UnsupportedArchive(ZipError::PASSWORD_REQUIRED) => Error::PasswordRequired,
+            err => Error::Zip(err),
+        };
+    }
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return match self {
+            Error::Io(err) => write!(f, "{}", err),
+            Error::Zip(err) => write!(f, "{}", err),
+            Error::NotFound => write!(f, "file not found in archive"),
+            Error::InvalidPassword => write!(f, "invalid password"),
+            Error::PasswordRequired => write!(f, "a password is required"),
+        };
+    }
+}
+
+/// Split `path` in the path of an archive and the name of a file inside it, for instance
+/// "tests/data/archive.zip/subdir/file.png" gives "tests/data/archive.zip" and "subdir/file.png".
+/// Returns `None` if no ancestor of `path` is an existing file.
+fn split_archive_path(path: &Path) -> Option<(PathBuf, String)> {
+    for archive in path.ancestors().skip(1) {
+        if archive.as_os_str().is_empty() || !archive.is_file() {
+            continue;
+        }
+
+        let member = path
+            .strip_prefix(archive)
+            .ok()?
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+
+        return Some((archive.to_path_buf(), member));
+    }
+
+    return None;
+}
+
+/// Read the file at `path`, where `path` may go through a zip archive as if it was a directory.
+/// Encrypted members, either with ZipCrypto or WinZip AES, are decrypted with `password`.
+pub fn read(path: &Path, password: Option<&str>) -> Result<Vec<u8>, Error> {
+    if path.is_file() {
+        return Ok(std::fs::read(path)?);
+    }
+
+    let (archive_path, member) = match split_archive_path(path) {
+        Some(result) => result,
+        None => return Err(Error::Io(std::io::ErrorKind::NotFound.into())),
+    };
+
+    let mut archive = ZipArchive::new(File::open(archive_path)?)?;
+    let mut file = if let Some(password) = password {
+        archive
+            .by_name_decrypt(member.as_str(), password.as_bytes())?
+            .map_err(|_err| Error::InvalidPassword)?
+    } else {
+        archive.by_name(member.as_str())?
+    };
+
+    let mut content = Vec::with_capacity(file.size() as usize);
+    file.read_to_end(&mut content)?;
+    return Ok(content);
+}
+
+/// List the files stored in the zip archive at `path`, in the order they appear in the archive.
+/// Directories are skipped and the names can be appended to `path` to be given to [`read`].
+pub fn list(path: &Path) -> Result<Vec<String>, Error> {
+    let mut archive = ZipArchive::new(File::open(path)?)?;
+    let mut names = Vec::with_capacity(archive.len());
+    for idx in 0..archive.len() {
+        let file = archive.by_index_raw(idx)?;
+        if !file.is_dir() {
+            names.push(file.name().to_string());
+        }
+    }
+    return Ok(names);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_plain_file() {
+        let content = read(Path::new("tests/vault/file.txt"), None).unwrap();
+        assert_eq!(content.as_slice(), b"file content");
+    }
+
+    #[test]
+    fn read_from_decrypted_archive() {
+        let content = read(Path::new("tests/vault/decrypted.zip/subdir/file.txt"), None).unwrap();
+        assert_eq!(content.as_slice(), b"file content");
+    }
+
+    #[test]
+    fn read_from_aes_encrypted_archive() {
+        let path = Path::new("tests/vault/encrypted-with-aes-and-password-is-password.zip/file.txt");
+        let content = read(path, Some("password")).unwrap();
+        assert_eq!(content.as_slice(), b"file content");
+    }
+
+    #[test]
+    fn wrong_password_is_detected() {
+        let path = Path::new("tests/vault/encrypted-with-aes-and-password-is-password.zip/file.txt");
+        assert!(matches!(read(path, Some("not the password")), Err(Error::InvalidPassword)));
+    }
+
+    #[test]
+    fn missing_password_is_detected() {
+        let path = Path::new("tests/vault/encrypted-with-aes-and-password-is-password.zip/file.txt");
+        assert!(matches!(read(path, None), Err(Error::PasswordRequired)));
+    }
+
+    #[test]
+    fn list_skips_directories() {
+        let names = list(Path::new("tests/vault/decrypted.zip")).unwrap();
+        assert_eq!(names, ["subdir/file.txt", "file.txt"]);
+    }
+
+    #[test]
+    fn missing_member_is_detected() {
+        let path = Path::new("tests/vault/decrypted.zip/missing.txt");
+        assert!(matches!(read(path, None), Err(Error::NotFound)));
+    }
+}
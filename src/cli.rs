@@ -0,0 +1,271 @@
+use crate::{archive, vault};
+use std::io::{BufRead, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use zeroize::Zeroizing;
+
+// Exit codes returned by the headless commands, so scripts can tell apart a source that
+// couldn't be read from a vault that doesn't contain the requested entry.
+const EXIT_OPEN_FAILED: u8 = 1;
+const EXIT_ENTRY_NOT_FOUND: u8 = 2;
+const EXIT_WRONG_PASSWORD: u8 = 3;
+const EXIT_INVALID_SECRET: u8 = 4;
+const EXIT_CORRUPT_DATABASE: u8 = 5;
+
+/// Exit code of the headless commands when reading the source failed with `err`.
+pub fn exit_code(err: &vault::Error) -> ExitCode {
+    let code = match err {
+        vault::Error::WrongPassword | vault::Error::PasswordRequired => EXIT_WRONG_PASSWORD,
+        vault::Error::ImageDecode(_)
+        | vault::Error::NoQrCode
+        | vault::Error::QrDecode(_)
+        | vault::Error::Url(_)
+        | vault::Error::InvalidEntry(..) => EXIT_INVALID_SECRET,
+        vault::Error::CorruptDatabase(_) => EXIT_CORRUPT_DATABASE,
+        _ => EXIT_OPEN_FAILED,
+    };
+
+    return ExitCode::from(code);
+}
+
+/// Ask the password on the terminal. When it can't be read, such as when no terminal is attached,
+/// the reason is printed and the exit code of a missing credential is returned.
+pub fn prompt_password() -> Result<Zeroizing<String>, ExitCode> {
+    if !std::io::stdin().is_terminal() {
+        eprintln!("A password is required, but there is no terminal to ask it on");
+        return Err(ExitCode::from(EXIT_WRONG_PASSWORD));
+    }
+
+    match rpassword::prompt_password("Enter password: ") {
+        Ok(password) => return Ok(Zeroizing::new(password)),
+        Err(err) => {
+            eprintln!("Failed to read the password, {}", err);
+            return Err(ExitCode::from(EXIT_WRONG_PASSWORD));
+        }
+    }
+}
+
+fn is_vault(path: &Path) -> bool {
+    return path
+        .extension()
+        .map(|ext| ext.eq_ignore_ascii_case("kdbx"))
+        .unwrap_or(false);
+}
+
+/// Match `name` against `pattern`, where '*' matches any sequence of characters, including '/',
+/// and '?' matches exactly one character.
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<char>>();
+    let name = name.chars().collect::<Vec<char>>();
+
+    let (mut p, mut n) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, n));
+            p += 1;
+        } else if let Some((star, matched)) = backtrack {
+            // Let the last '*' consume one more character and retry from there.
+            backtrack = Some((star, matched + 1));
+            p = star + 1;
+            n = matched + 1;
+        } else {
+            return false;
+        }
+    }
+
+    return pattern[p..].iter().all(|c| *c == '*');
+}
+
+/// List the files of the zip archive at `path` and let the user pick one in the terminal, either
+/// by its index or with a glob pattern matching a single file. Returns the path of the selected
+/// file inside the archive, or `None` if the user cancelled by entering an empty line.
+pub fn pick_archive_member(path: &str) -> Option<PathBuf> {
+    let names = match archive::list(Path::new(path)) {
+        Ok(names) => names,
+        Err(err) => {
+            eprintln!("Failed to list the files of '{}', err: {}", path, err);
+            return None;
+        }
+    };
+
+    if names.is_empty() {
+        eprintln!("The archive '{}' doesn't contain any file", path);
+        return None;
+    }
+
+    for (idx, name) in names.iter().enumerate() {
+        println!("[{}] {}", idx + 1, name);
+    }
+
+    let stdin = std::io::stdin();
+    let mut lines = stdin.lock().lines();
+    loop {
+        print!("Select a file by index or pattern: ");
+        let _ = std::io::stdout().flush();
+
+        let line = match lines.next() {
+            Some(Ok(line)) => line,
+            _ => return None,
+        };
+
+        let answer = line.trim();
+        if answer.is_empty() {
+            return None;
+        }
+
+        if let Ok(idx) = answer.parse::<usize>() {
+            if 1 <= idx && idx <= names.len() {
+                return Some(Path::new(path).join(names[idx - 1].as_str()));
+            }
+
+            eprintln!("Index {} is out of range, expected 1 to {}", idx, names.len());
+            continue;
+        }
+
+        let matches = names
+            .iter()
+            .filter(|name| glob_match(answer, name.as_str()))
+            .collect::<Vec<&String>>();
+
+        match matches.as_slice() {
+            [] => eprintln!("No file matches '{}'", answer),
+            [name] => return Some(Path::new(path).join(name.as_str())),
+            matches => {
+                eprintln!("'{}' matches {} files:", answer, matches.len());
+                for name in matches {
+                    eprintln!("    {}", name);
+                }
+            }
+        }
+    }
+}
+
+/// Print the current code of `source` on stdout, `source` being either a QR code image or a
+/// KeePass database. When the database contains more than one secret, `entry` selects the one
+/// to print by name, otherwise every secret is printed on its own line followed by its name.
+pub fn code(
+    source: &Path,
+    entry: Option<&str>,
+    password: Option<Zeroizing<String>>,
+    keyfile: Option<&Path>,
+) -> ExitCode {
+    let path = source.to_path_buf();
+    let source = source.display();
+
+    if !is_vault(path.as_path()) {
+        return match vault::VaultSecret::from_path(path.as_path(), password.as_deref().map(String::as_str)) {
+            Ok(secret) => {
+                println!("{}", secret.code());
+                ExitCode::SUCCESS
+            }
+            Err(err) => {
+                eprintln!("Failed to read a secret from '{}', {}", source, err);
+                exit_code(&err)
+            }
+        };
+    }
+
+    // The password isn't asked for a vault that can't be opened anyway.
+    if let Err(err) = std::fs::metadata(path.as_path()) {
+        let err = vault::Error::Io(path.clone(), err);
+        eprintln!("Failed to open the vault '{}', {}", source, err);
+        return exit_code(&err);
+    }
+
+    let password = match password {
+        Some(password) => Some(password),
+        // A database protected only by a key file doesn't need a password.
+        None if keyfile.is_some() => None,
+        None => match prompt_password() {
+            Ok(password) => Some(password),
+            Err(code) => return code,
+        },
+    };
+
+    let vault = match vault::Vault::open(path, password.as_deref().map(String::as_str), keyfile) {
+        Ok(vault) => vault,
+        Err(err) => {
+            eprintln!("Failed to open the vault '{}', {}", source, err);
+            return exit_code(&err);
+        }
+    };
+
+    let (secrets, errors) = vault.secrets();
+    for err in errors.iter() {
+        eprintln!("Skipped an entry of '{}', {}", source, err);
+    }
+
+    if let Some(entry) = entry {
+        if let Some(secret) = secrets.iter().find(|secret| secret.name == entry) {
+            println!("{}", secret.code());
+            return ExitCode::SUCCESS;
+        }
+
+        // The entry exists, but its secret is invalid.
+        let invalid = errors.iter().find(|err| matches!(err, vault::Error::InvalidEntry(name, _) if name == entry));
+        if let Some(err) = invalid {
+            return exit_code(err);
+        }
+
+        eprintln!("No entry named '{}' in '{}'", entry, source);
+        return ExitCode::from(EXIT_ENTRY_NOT_FOUND);
+    }
+
+    match secrets.as_slice() {
+        [] => {
+            eprintln!("No secret found in '{}'", source);
+            return ExitCode::from(EXIT_ENTRY_NOT_FOUND);
+        }
+        [secret] => println!("{}", secret.code()),
+        secrets => {
+            for secret in secrets {
+                println!("{} {}", secret.code(), secret.name);
+            }
+        }
+    }
+
+    return ExitCode::SUCCESS;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{exit_code, glob_match};
+    use crate::{otpauth, vault};
+    use keepass::error::DatabaseOpenError;
+    use std::process::ExitCode;
+
+    #[test]
+    fn glob_matches_literal_names() {
+        assert!(glob_match("file.txt", "file.txt"));
+        assert!(!glob_match("file.txt", "subdir/file.txt"));
+    }
+
+    #[test]
+    fn glob_matches_wildcards() {
+        assert!(glob_match("*.png", "noreply.example.png"));
+        assert!(glob_match("*/file.txt", "subdir/file.txt"));
+        assert!(glob_match("file.???", "file.txt"));
+        assert!(glob_match("*e*e*", "noreply.example.png"));
+        assert!(!glob_match("*.png", "noreply.example.jpg"));
+        assert!(!glob_match("file.??", "file.txt"));
+    }
+
+    #[test]
+    fn errors_have_distinct_exit_codes() {
+        let io = std::io::Error::from(std::io::ErrorKind::NotFound);
+        assert_eq!(exit_code(&vault::Error::Io("missing.png".into(), io)), ExitCode::from(1));
+        assert_eq!(exit_code(&vault::Error::WrongPassword), ExitCode::from(3));
+        assert_eq!(exit_code(&vault::Error::PasswordRequired), ExitCode::from(3));
+        assert_eq!(exit_code(&vault::Error::ImageDecode("invalid")), ExitCode::from(4));
+        assert_eq!(exit_code(&vault::Error::NoQrCode), ExitCode::from(4));
+        assert_eq!(exit_code(&vault::Error::Url(otpauth::ParseError::InvalidScheme)), ExitCode::from(4));
+        let invalid_entry = vault::Error::InvalidEntry("name".to_string(), otpauth::ParseError::IncompleteQuery);
+        assert_eq!(exit_code(&invalid_entry), ExitCode::from(4));
+        let corrupt = vault::Error::CorruptDatabase(DatabaseOpenError::UnsupportedVersion);
+        assert_eq!(exit_code(&corrupt), ExitCode::from(5));
+    }
+}
@@ -0,0 +1,99 @@
+use arboard::Clipboard;
+use std::time::{Duration, Instant};
+use zeroize::Zeroizing;
+
+use crate::{otpauth, vault::{self, VaultSecret}};
+
+#[cfg(windows)]
+use arboard::SetExtWindows;
+
+/// Longest delay before copied text is cleared, in seconds.
+pub const MAX_CLEAR_SECONDS: u64 = 60 * 60;
+
+fn open() -> Result<Clipboard, vault::Error> {
+    return Clipboard::new().map_err(vault::Error::Clipboard);
+}
+
+/// Read a secret from the clipboard, either an otpauth URL copied as text, or a QR code in a
+/// copied image such as a screenshot snippet.
+pub fn read_secret() -> Result<VaultSecret, vault::Error> {
+    let mut clipboard = open()?;
+
+    // The text may be an otpauth URL, which contains the secret.
+    if let Ok(text) = clipboard.get_text().map(Zeroizing::new) {
+        match otpauth::ParsedUrl::parse(text.trim()) {
+            Ok(parsed_url) => return Ok(VaultSecret::from_parsed_url(parsed_url)),
+            // Without an image either, the text was most likely meant to be a URL.
+            Err(err) => match read_image(&mut clipboard) {
+                Err(vault::Error::Clipboard(_)) => return Err(vault::Error::Url(err)),
+                result => return result,
+            },
+        }
+    }
+
+    return read_image(&mut clipboard);
+}
+
+fn read_image(clipboard: &mut Clipboard) -> Result<VaultSecret, vault::Error> {
+    let image = clipboard.get_image().map_err(vault::Error::Clipboard)?;
+
+    let mut pixels = Vec::with_capacity(image.width * image.height);
+    for rgba in image.bytes.chunks_exact(4) {
+        let r = rgba[0] as f32;
+        let g = rgba[1] as f32;
+        let b = rgba[2] as f32;
+        let grey = (0.299 * r + 0.587 * g + 0.114 * b) as u8;
+        pixels.push(grey);
+    }
+
+    return VaultSecret::from_slice(image.width, image.height, pixels.as_slice());
+}
+
+/// Text copied from the window, such as a code or a secret. It's cleared from the clipboard after
+/// a delay, unless something else was copied in the meantime.
+pub struct CopiedText {
+    // Kept open, because on X11 the content is only served while the clipboard is alive.
+    clipboard: Clipboard,
+    text: Zeroizing<String>,
+    clear_at: Option<Instant>,
+}
+
+impl CopiedText {
+    /// Copy `text` to the clipboard, marked as sensitive where the platform supports it, so
+    /// clipboard managers don't record it. `None`, or a delay too long to be represented, never
+    /// clears it.
+    pub fn copy(text: &str, clear_after: Option<Duration>) -> Result<Self, vault::Error> {
+        let mut clipboard = open()?;
+
+        let set = clipboard.set();
+        #[cfg(windows)]
+        let set = set.exclude_from_monitoring();
+
+        set.text(text).map_err(vault::Error::Clipboard)?;
+
+        return Ok(CopiedText {
+            clipboard,
+            text: Zeroizing::new(text.to_string()),
+            clear_at: clear_after.and_then(|delay| Instant::now().checked_add(delay)),
+        });
+    }
+
+    /// Time left before the clipboard is cleared, `None` if it's never cleared.
+    pub fn remaining(&self) -> Option<Duration> {
+        return self.clear_at.map(|clear_at| clear_at.saturating_duration_since(Instant::now()));
+    }
+
+    /// Clear the clipboard if it still holds the copied text.
+    pub fn clear(mut self) {
+        let current = match self.clipboard.get_text() {
+            Ok(current) => Zeroizing::new(current),
+            Err(_) => return,
+        };
+
+        if *current == *self.text {
+            if let Err(err) = self.clipboard.clear() {
+                eprintln!("Failed to clear the clipboard, err: {}", err);
+            }
+        }
+    }
+}
@@ -0,0 +1,125 @@
+use crate::sha1;
+use sha2::{Digest, Sha256, Sha512};
+
+const SHA256_BLOCK_SIZE: usize = 64;
+const SHA512_BLOCK_SIZE: usize = 128;
+
+/// Hash function used to compute the HMAC of a one-time password, as specified by the
+/// `algorithm` parameter of an otpauth URL.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum Algorithm {
+    #[default]
+    Sha1,
+    Sha256,
+    Sha512,
+}
+
+impl Algorithm {
+    pub fn from_name(name: &str) -> Option<Self> {
+        if name.eq_ignore_ascii_case("SHA1") {
+            return Some(Algorithm::Sha1);
+        } else if name.eq_ignore_ascii_case("SHA256") {
+            return Some(Algorithm::Sha256);
+        } else if name.eq_ignore_ascii_case("SHA512") {
+            return Some(Algorithm::Sha512);
+        } else {
+            return None;
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        return match self {
+            Algorithm::Sha1 => "SHA1",
+            Algorithm::Sha256 => "SHA256",
+            Algorithm::Sha512 => "SHA512",
+        };
+    }
+}
+
+fn hmac_with<D: Digest, const BLOCK_SIZE: usize>(secret: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut key = [0u8; BLOCK_SIZE];
+
+    if BLOCK_SIZE < secret.len() {
+        let new_secret = D::digest(secret);
+        key[..new_secret.len()].copy_from_slice(&new_secret);
+    } else {
+        key[..secret.len()].copy_from_slice(secret);
+    }
+
+    let mut ipad = [0x36u8; BLOCK_SIZE];
+    let mut opad = [0x5Cu8; BLOCK_SIZE];
+
+    for i in 0..BLOCK_SIZE {
+        ipad[i] ^= key[i];
+        opad[i] ^= key[i];
+    }
+
+    let mut cipher = D::new();
+    cipher.update(ipad);
+    cipher.update(data);
+    let stage1 = cipher.finalize();
+
+    let mut cipher = D::new();
+    cipher.update(opad);
+    cipher.update(stage1);
+
+    return cipher.finalize().to_vec();
+}
+
+pub fn hmac(algorithm: Algorithm, secret: &[u8], data: &[u8]) -> Vec<u8> {
+    return match algorithm {
+        Algorithm::Sha1 => sha1::hmac(secret, data).to_vec(),
+        Algorithm::Sha256 => hmac_with::<Sha256, SHA256_BLOCK_SIZE>(secret, data),
+        Algorithm::Sha512 => hmac_with::<Sha512, SHA512_BLOCK_SIZE>(secret, data),
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test cases 1 and 6 from RFC 4231.
+    #[test]
+    fn test_sha256_with_a_short_key() {
+        let digest = hmac(Algorithm::Sha256, &[0x0b; 20], b"Hi There");
+        assert_eq!(
+            digest.as_slice(),
+            b"\xB0\x34\x4C\x61\xD8\xDB\x38\x53\x5C\xA8\xAF\xCE\xAF\x0B\xF1\x2B\x88\x1D\xC2\x00\xC9\x83\x3D\xA7\x26\xE9\x37\x6C\x2E\x32\xCF\xF7"
+        );
+    }
+
+    #[test]
+    fn test_sha512_with_a_short_key() {
+        let digest = hmac(Algorithm::Sha512, &[0x0b; 20], b"Hi There");
+        assert_eq!(
+            digest.as_slice(),
+            b"\x87\xAA\x7C\xDE\xA5\xEF\x61\x9D\x4F\xF0\xB4\x24\x1A\x1D\x6C\xB0\x23\x79\xF4\xE2\xCE\x4E\xC2\x78\x7A\xD0\xB3\x05\x45\xE1\x7C\xDE\xDA\xA8\x33\xB7\xD6\xB8\xA7\x02\x03\x8B\x27\x4E\xAE\xA3\xF4\xE4\xBE\x9D\x91\x4E\xEB\x61\xF1\x70\x2E\x69\x6C\x20\x3A\x12\x68\x54"
+        );
+    }
+
+    #[test]
+    fn test_sha256_with_a_key_larger_than_the_block() {
+        let digest = hmac(
+            Algorithm::Sha256,
+            &[0xaa; 131],
+            b"Test Using Larger Than Block-Size Key - Hash Key First",
+        );
+        assert_eq!(
+            digest.as_slice(),
+            b"\x60\xE4\x31\x59\x1E\xE0\xB6\x7F\x0D\x8A\x26\xAA\xCB\xF5\xB7\x7F\x8E\x0B\xC6\x21\x37\x28\xC5\x14\x05\x46\x04\x0F\x0E\xE3\x7F\x54"
+        );
+    }
+
+    #[test]
+    fn test_sha512_with_a_key_larger_than_the_block() {
+        let digest = hmac(
+            Algorithm::Sha512,
+            &[0xaa; 131],
+            b"Test Using Larger Than Block-Size Key - Hash Key First",
+        );
+        assert_eq!(
+            digest.as_slice(),
+            b"\x80\xB2\x42\x63\xC7\xC1\xA3\xEB\xB7\x14\x93\xC1\xDD\x7B\xE8\xB4\x9B\x46\xD1\xF4\x1B\x4A\xEE\xC1\x12\x1B\x01\x37\x83\xF8\xF3\x52\x6B\x56\xD0\x37\xE0\x5F\x25\x98\xBD\x0F\xD2\x21\x5D\x6A\x1E\x52\x95\xE6\x4F\x73\xF6\x3F\x0A\xEC\x8B\x91\x5A\x98\x5D\x78\x65\x98"
+        );
+    }
+}
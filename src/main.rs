@@ -3,47 +3,121 @@
   windows_subsystem = "windows"
 )]
 
-use clap::Parser;
+use clap::{Parser, Subcommand};
+use std::path::PathBuf;
+use std::process::ExitCode;
+use zeroize::Zeroizing;
 
 mod base32;
+mod hmac;
 mod otpauth;
 mod sha1;
 mod stb_image;
 mod totp;
 mod vault;
 mod app;
+mod archive;
+mod clipboard;
 mod password;
+mod secret;
+mod settings;
 mod sys;
+mod cli;
 
 #[derive(Parser, Debug)]
-#[clap(author, version, about, long_about = None)]
+#[clap(author, version, about, long_about = None, args_conflicts_with_subcommands = true)]
 struct Args {
+    #[clap(subcommand)]
+    command: Option<Command>,
+
     /// Path on disk if the file to be signed.
     #[clap(value_name = "file", index = 1)]
     input: Option<String>,
 
     /// Optional password if the file is stored in a encrypted zip.
-    #[clap(short, long, value_name = "password")]
+    #[clap(short, long, value_name = "password", global = true)]
     password: Option<Option<String>>,
 
+    /// Optional key file, alone or with the password, protecting the KeePass database.
+    #[clap(short, long, value_name = "keyfile", global = true)]
+    keyfile: Option<PathBuf>,
+
     /// When in interactive mode, stip will list available files from the zip file.
-    #[clap(short, long)]
+    #[clap(short, long, global = true)]
     interactive: bool,
 }
 
-fn main() {
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Print the current code and exit, without opening a window.
+    Code {
+        /// QR code image or KeePass database (.kdbx) to read the secret from.
+        #[clap(value_name = "source")]
+        source: String,
+
+        /// Name of the entry to print when the database contains more than one secret.
+        #[clap(short, long, value_name = "entry")]
+        entry: Option<String>,
+    },
+}
+
+fn main() -> ExitCode {
     let args = Args::parse();
 
+    // Secrets and passwords are wiped from memory once dropped, but a core dump taken while
+    // they are alive would still contain them.
+    sys::disable_core_dumps();
+
+    let settings = settings::Settings::load();
+    totp::set_time_offset(settings.time_offset);
+
     // First check if "-p" or "--password" was specified.
     // When "-p" is specified, and there is still no value, simply prompt for it.
-    let password = args.password.map(|password| {
-        return password.unwrap_or_else(|| {
-            return rpassword::prompt_password("Enter password: ")
-                .expect("Failed to read user password");
-        });
-    });
-
-    if app::build(args.input.as_deref(), password).is_err() {
+    let password = match args.password {
+        Some(Some(password)) => Some(Zeroizing::new(password)),
+        Some(None) => match cli::prompt_password() {
+            Ok(password) => Some(password),
+            Err(code) => return code,
+        },
+        None => None,
+    };
+
+    if let Some(Command::Code { source, entry }) = args.command {
+        let source = if args.interactive {
+            match cli::pick_archive_member(source.as_str()) {
+                Some(path) => path,
+                None => return ExitCode::FAILURE,
+            }
+        } else {
+            PathBuf::from(source)
+        };
+
+        return cli::code(source.as_path(), entry.as_deref(), password, args.keyfile.as_deref());
+    }
+
+    // In interactive mode, the input is an archive from which the user picks a QR code, so
+    // the window opens with this secret rather than with a database.
+    let mut secrets = Vec::new();
+    let mut input = args.input.as_deref();
+    if let (true, Some(archive)) = (args.interactive, input.take()) {
+        let path = match cli::pick_archive_member(archive) {
+            Some(path) => path,
+            None => return ExitCode::FAILURE,
+        };
+
+        match vault::VaultSecret::from_path(path.as_path(), password.as_deref().map(String::as_str)) {
+            Ok(secret) => secrets.push(secret),
+            Err(err) => {
+                eprintln!("Failed to read a secret from '{}', {}", path.display(), err);
+                return cli::exit_code(&err);
+            }
+        }
+    }
+
+    if app::build(input, password, args.keyfile, secrets, settings).is_err() {
         eprintln!("Failed to open input '{:?}'", args.input);
+        return ExitCode::FAILURE;
     }
+
+    return ExitCode::SUCCESS;
 }
@@ -1,7 +1,8 @@
 #![allow(clippy::needless_return)]
 
-use crate::base32;
+use crate::{base32, hmac::Algorithm, secret::SecretBytes};
 use url::{form_urlencoded, Host, Url};
+use zeroize::Zeroizing;
 
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub enum ParseError {
@@ -10,21 +11,78 @@ pub enum ParseError {
     InvalidDomain,
     IncompleteQuery,
     NoIssuer,
+    /// The issuer contains ':', which separates it from the account name in the label.
+    InvalidIssuer,
+    UnsupportedAlgorithm,
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return match self {
+            ParseError::InvalidUrl => write!(f, "the URL is invalid"),
+            ParseError::InvalidScheme => write!(f, "the scheme isn't otpauth"),
+            ParseError::InvalidDomain => write!(f, "the type isn't totp or hotp"),
+            ParseError::IncompleteQuery => write!(f, "a parameter is missing"),
+            ParseError::NoIssuer => write!(f, "the issuer is missing"),
+            ParseError::InvalidIssuer => write!(f, "the issuer contains ':'"),
+            ParseError::UnsupportedAlgorithm => write!(f, "the algorithm isn't supported"),
+        };
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum OtpKind {
+    Totp,
+    Hotp,
+}
+
+/// How the truncated HMAC is turned into the code shown to the user.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Encoder {
+    Decimal,
+    /// Steam Guard codes, made of 5 characters instead of decimal digits.
+    Steam,
 }
 
 #[derive(Debug, Clone)]
 pub struct ParsedUrl {
-    pub raw: String,
+    /// The URL contains the secret, so it's wiped from memory as well.
+    pub raw: Zeroizing<String>,
     pub account_name: String,
     pub issuer: String,
-    pub secret: Vec<u8>,
+    pub secret: SecretBytes,
     pub period: u64,
     pub digits: usize,
+    pub algorithm: Algorithm,
+    pub kind: OtpKind,
+    pub encoder: Encoder,
+    /// Moving factor of the next HOTP code, always 0 for TOTP secrets.
+    pub counter: u64,
 }
 
 impl ParsedUrl {
-    pub fn parse(path: String) -> Result<ParsedUrl, ParseError> {
-        let res = Url::parse(path.as_str()).map_err(|_err| {
+    pub fn parse(path: &str) -> Result<ParsedUrl, ParseError> {
+        // Steam secrets exported by some tools are simply "steam://{secret}".
+        if let Some(secret) = path.strip_prefix("steam://") {
+            let secret = base32::b32decode(secret.as_bytes()).map_err(|_err| {
+                return ParseError::IncompleteQuery;
+            })?;
+
+            return Ok(ParsedUrl {
+                raw: Zeroizing::new(path.to_string()),
+                account_name: String::new(),
+                issuer: "Steam".to_string(),
+                secret: SecretBytes::new(secret),
+                period: 30,
+                digits: 5,
+                algorithm: Algorithm::Sha1,
+                kind: OtpKind::Totp,
+                encoder: Encoder::Steam,
+                counter: 0,
+            });
+        }
+
+        let res = Url::parse(path).map_err(|_err| {
             return ParseError::InvalidUrl;
         })?;
 
@@ -32,9 +90,11 @@ impl ParsedUrl {
             return Err(ParseError::InvalidScheme);
         }
 
-        if res.host() != Some(Host::Domain("totp")) {
-            return Err(ParseError::InvalidDomain);
-        }
+        let kind = match res.host() {
+            Some(Host::Domain("totp")) => OtpKind::Totp,
+            Some(Host::Domain("hotp")) => OtpKind::Hotp,
+            _ => return Err(ParseError::InvalidDomain),
+        };
 
         let label = urlencoding::decode(res.path().trim_start_matches('/'))
             .map_err(|_err| {
@@ -53,13 +113,17 @@ impl ParsedUrl {
         let mut secret = None;
         let mut digits = 6;
         let mut period = 30;
+        let mut algorithm = Algorithm::default();
+        let mut counter = 0;
+        let mut encoder = Encoder::Decimal;
 
         let query = res.query().ok_or(ParseError::IncompleteQuery)?;
         for (key, val) in form_urlencoded::parse(query.as_ref()) {
             if key == "secret" {
-                secret = Some(base32::b32decode(val.as_ref().as_bytes()).map_err(|_err| {
+                let decoded = base32::b32decode(val.as_ref().as_bytes()).map_err(|_err| {
                     return ParseError::IncompleteQuery;
-                })?);
+                })?;
+                secret = Some(SecretBytes::new(decoded));
             } else if key == "issuer" {
                 issuer = Some(val.into_owned());
             } else if key == "digits" {
@@ -67,6 +131,16 @@ impl ParsedUrl {
                     eprintln!("Failed to parse '{}' as usize in base 10, err: {}", val, err);
                     return ParseError::InvalidUrl;
                 })?;
+            } else if key == "algorithm" {
+                algorithm = Algorithm::from_name(&val).ok_or(ParseError::UnsupportedAlgorithm)?;
+            } else if key == "counter" && kind == OtpKind::Hotp {
+                counter = u64::from_str_radix(&val, 10).map_err(|err| {
+                    eprintln!("Failed to parse '{}' as u64 in base 10, err: {}", val, err);
+                    return ParseError::InvalidUrl;
+                })?;
+            } else if key == "encoder" && val.eq_ignore_ascii_case("steam") {
+                encoder = Encoder::Steam;
+                digits = 5;
             } else if key == "period" {
                 period = u64::from_str_radix(&val, 10).map_err(|err| {
                     eprintln!("Failed to parse '{}' as u64 in base 10, err: {}", val, err);
@@ -79,15 +153,99 @@ impl ParsedUrl {
             return Err(ParseError::NoIssuer);
         }
 
+        if period == 0 {
+            eprintln!("The period of a TOTP secret can't be 0");
+            return Err(ParseError::InvalidUrl);
+        }
+
         return Ok(ParsedUrl {
-            raw: path,
+            raw: Zeroizing::new(path.to_string()),
             account_name,
             issuer: issuer.ok_or(ParseError::IncompleteQuery)?,
             secret: secret.ok_or(ParseError::IncompleteQuery)?,
             period,
             digits,
+            algorithm,
+            kind,
+            encoder,
+            counter,
         });
     }
+
+    /// Build a TOTP secret from its parameters, typically entered by the user when the provider
+    /// shows the base32 secret rather than a QR code. The otpauth URL is built accordingly.
+    pub fn from_parts(
+        issuer: &str,
+        account_name: &str,
+        secret: &str,
+        period: u64,
+        digits: usize,
+        algorithm: Algorithm,
+    ) -> Result<ParsedUrl, ParseError> {
+        if issuer.contains(':') {
+            return Err(ParseError::InvalidIssuer);
+        }
+
+        let secret = Zeroizing::new(secret.split_whitespace().collect::<String>());
+        let url = Zeroizing::new(format!(
+            "otpauth://totp/{}:{}?secret={}&issuer={}&period={}&digits={}&algorithm={}",
+            urlencoding::encode(issuer),
+            urlencoding::encode(account_name),
+            urlencoding::encode(secret.as_str()),
+            urlencoding::encode(issuer),
+            period,
+            digits,
+            algorithm.name(),
+        ));
+
+        return ParsedUrl::parse(url.as_str());
+    }
+
+    /// Build a TOTP secret from the "TOTP Seed" and "TOTP Settings" fields of legacy KeePass
+    /// plugins. The settings are "{period};{digits}", the digits being "S" for Steam Guard codes,
+    /// and default to "30;6" when missing.
+    pub fn from_seed(seed: &str, settings: Option<&str>, issuer: &str) -> Result<ParsedUrl, ParseError> {
+        let seed = Zeroizing::new(seed.split_whitespace().collect::<String>());
+
+        let mut settings = settings.unwrap_or("30;6").split(';');
+        let period = settings.next().map(str::trim).filter(|period| !period.is_empty()).unwrap_or("30");
+        let digits = settings.next().map(str::trim).filter(|digits| !digits.is_empty()).unwrap_or("6");
+
+        let (digits, encoder) = if digits.eq_ignore_ascii_case("S") {
+            ("5", "&encoder=steam")
+        } else {
+            (digits, "")
+        };
+
+        let url = Zeroizing::new(format!(
+            "otpauth://totp/{}?secret={}&issuer={}&period={}&digits={}{}",
+            urlencoding::encode(issuer),
+            urlencoding::encode(seed.as_str()),
+            urlencoding::encode(issuer),
+            urlencoding::encode(period),
+            urlencoding::encode(digits),
+            encoder,
+        ));
+
+        return ParsedUrl::parse(url.as_str());
+    }
+
+    /// Update the HOTP counter, rewriting the "counter" query parameter of the raw URL while
+    /// leaving the other parameters untouched.
+    pub fn set_counter(&mut self, counter: u64) {
+        let (base, query) = self.raw.split_once('?').unwrap_or((self.raw.as_str(), ""));
+        let mut params = query
+            .split('&')
+            .filter(|param| !param.is_empty() && !param.starts_with("counter="))
+            .collect::<Vec<&str>>();
+
+        let counter_param = format!("counter={}", counter);
+        params.push(counter_param.as_str());
+
+        let query = Zeroizing::new(params.join("&"));
+        self.raw = Zeroizing::new(format!("{}?{}", base, query.as_str()));
+        self.counter = counter;
+    }
 }
 
 #[cfg(test)]
@@ -105,6 +263,105 @@ mod tests {
         );
         assert_eq!(res.digits, 10);
         assert_eq!(res.period, 35);
+        assert_eq!(res.algorithm, Algorithm::Sha1);
+    }
+
+    #[test]
+    fn algorithm_is_a_query_value() {
+        let res = ParsedUrl::parse("otpauth://totp/Company%3Aexample%40company.com?secret=gkjeixzp5xmm37meoimq====&algorithm=SHA256").unwrap();
+        assert_eq!(res.algorithm, Algorithm::Sha256);
+
+        let res = ParsedUrl::parse("otpauth://totp/Company%3Aexample%40company.com?secret=gkjeixzp5xmm37meoimq====&algorithm=sha512").unwrap();
+        assert_eq!(res.algorithm, Algorithm::Sha512);
+    }
+
+    #[test]
+    fn hotp_with_counter() {
+        let res = ParsedUrl::parse("otpauth://hotp/Company%3Aexample%40company.com?secret=gkjeixzp5xmm37meoimq====&counter=42").unwrap();
+        assert_eq!(res.kind, OtpKind::Hotp);
+        assert_eq!(res.counter, 42);
+    }
+
+    #[test]
+    fn set_counter_rewrites_the_url() {
+        let mut res = ParsedUrl::parse("otpauth://hotp/Company%3Aexample%40company.com?secret=gkjeixzp5xmm37meoimq====&counter=42&digits=8").unwrap();
+        res.set_counter(43);
+        assert_eq!(
+            res.raw.as_str(),
+            "otpauth://hotp/Company%3Aexample%40company.com?secret=gkjeixzp5xmm37meoimq====&digits=8&counter=43"
+        );
+
+        let res = ParsedUrl::parse(res.raw.as_str()).unwrap();
+        assert_eq!(res.counter, 43);
+        assert_eq!(res.digits, 8);
+    }
+
+    #[test]
+    fn steam_encoder() {
+        let res = ParsedUrl::parse("otpauth://totp/Steam%3Aname?secret=gkjeixzp5xmm37meoimq====&issuer=Steam&encoder=steam").unwrap();
+        assert_eq!(res.encoder, Encoder::Steam);
+        assert_eq!(res.digits, 5);
+
+        let res = ParsedUrl::parse("steam://gkjeixzp5xmm37meoimq====").unwrap();
+        assert_eq!(res.encoder, Encoder::Steam);
+        assert_eq!(res.issuer.as_str(), "Steam");
+        assert_eq!(
+            res.secret.as_slice(),
+            b"\x32\x92\x44\x5F\x2F\xED\xD8\xCD\xFD\x84\x72\x19"
+        );
+    }
+
+    #[test]
+    fn secret_from_parts() {
+        let res = ParsedUrl::from_parts("Big Tech", "name@example.com", "gkje ixzp 5xmm 37me oimq", 60, 8, Algorithm::Sha256).unwrap();
+        assert_eq!(res.issuer.as_str(), "Big Tech");
+        assert_eq!(res.account_name.as_str(), "name@example.com");
+        assert_eq!(
+            res.secret.as_slice(),
+            b"\x32\x92\x44\x5F\x2F\xED\xD8\xCD\xFD\x84\x72\x19"
+        );
+        assert_eq!(res.period, 60);
+        assert_eq!(res.digits, 8);
+        assert_eq!(res.algorithm, Algorithm::Sha256);
+
+        let res = ParsedUrl::parse(res.raw.as_str()).unwrap();
+        assert_eq!(res.issuer.as_str(), "Big Tech");
+        assert_eq!(res.account_name.as_str(), "name@example.com");
+
+        let res = ParsedUrl::from_parts("Big:Tech", "name@example.com", "gkjeixzp5xmm37meoimq", 30, 6, Algorithm::Sha1);
+        assert_eq!(res.unwrap_err(), ParseError::InvalidIssuer);
+    }
+
+    #[test]
+    fn secret_from_legacy_seed() {
+        let res = ParsedUrl::from_seed("GKJE IXZP 5XMM 37ME OIMQ", Some("60;8"), "Big Tech").unwrap();
+        assert_eq!(res.issuer.as_str(), "Big Tech");
+        assert_eq!(
+            res.secret.as_slice(),
+            b"\x32\x92\x44\x5F\x2F\xED\xD8\xCD\xFD\x84\x72\x19"
+        );
+        assert_eq!(res.period, 60);
+        assert_eq!(res.digits, 8);
+        assert_eq!(res.encoder, Encoder::Decimal);
+
+        let res = ParsedUrl::from_seed("gkjeixzp5xmm37meoimq", Some("30;S"), "Steam").unwrap();
+        assert_eq!(res.encoder, Encoder::Steam);
+        assert_eq!(res.digits, 5);
+
+        let res = ParsedUrl::from_seed("gkjeixzp5xmm37meoimq", None, "Company").unwrap();
+        assert_eq!(res.period, 30);
+        assert_eq!(res.digits, 6);
+    }
+
+    #[test]
+    fn unsupported_algorithm() {
+        assert_eq!(
+            ParsedUrl::parse(
+                "otpauth://totp/Company%3Aexample%40company.com?secret=gkjeixzp5xmm37meoimq====&algorithm=MD5"
+            )
+            .unwrap_err(),
+            ParseError::UnsupportedAlgorithm,
+        );
     }
 
     #[test]
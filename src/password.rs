@@ -1,3 +1,35 @@
+fn fields_id() -> egui::Id {
+    return egui::Id::new("password_fields");
+}
+
+/// Drop the state egui keeps for the password fields, and the other fields registered with
+/// `protect_field`, which contains the text of the field, once the window showing them is closed.
+pub fn forget_fields(ctx: &egui::Context) {
+    ctx.data_mut(|d| {
+        let fields = d.get_temp::<Vec<egui::Id>>(fields_id()).unwrap_or_default();
+        for field_id in fields {
+            d.remove::<egui::text_edit::TextEditState>(field_id);
+        }
+        d.remove::<Vec<egui::Id>>(fields_id());
+    });
+}
+
+/// Drop the undo history of the field `field_id`, which keeps copies of its text that aren't
+/// wiped, and register the field so `forget_fields` drops its whole state.
+pub fn protect_field(ctx: &egui::Context, field_id: egui::Id) {
+    if let Some(mut state) = egui::text_edit::TextEditState::load(ctx, field_id) {
+        state.clear_undoer();
+        state.store(ctx, field_id);
+    }
+
+    ctx.data_mut(|d| {
+        let fields = d.get_temp_mut_or_default::<Vec<egui::Id>>(fields_id());
+        if !fields.contains(&field_id) {
+            fields.push(field_id);
+        }
+    });
+}
+
 pub fn password_ui(ui: &mut egui::Ui, password: &mut String, enable_error_border: bool) -> egui::Response {
     // This widget has its own state - show or hide password characters (`show_plaintext`).
     // In this case we use a simple `bool`, but you can also declare your own type.
@@ -36,6 +68,8 @@ pub fn password_ui(ui: &mut egui::Ui, password: &mut String, enable_error_border
         );
     });
 
+    protect_field(ui.ctx(), result.inner.id);
+
     // Store the (possibly changed) state:
     ui.data_mut(|d| d.insert_temp(state_id, show_plaintext));
 
@@ -0,0 +1,58 @@
+use std::ops::Deref;
+use zeroize::Zeroize;
+
+use crate::sys;
+
+/// Bytes of a secret, such as the seed of a TOTP secret. The memory is locked, so it's never
+/// written to the swap, and wiped when dropped.
+pub struct SecretBytes(Vec<u8>);
+
+impl SecretBytes {
+    pub fn new(bytes: Vec<u8>) -> Self {
+        sys::lock_memory(bytes.as_slice());
+        return SecretBytes(bytes);
+    }
+
+    pub fn as_slice(&self) -> &[u8] {
+        return self.0.as_slice();
+    }
+}
+
+impl Clone for SecretBytes {
+    fn clone(&self) -> Self {
+        return SecretBytes::new(self.0.clone());
+    }
+}
+
+impl Deref for SecretBytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        return self.0.as_slice();
+    }
+}
+
+impl std::fmt::Debug for SecretBytes {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return write!(f, "SecretBytes({} bytes)", self.0.len());
+    }
+}
+
+impl Drop for SecretBytes {
+    fn drop(&mut self) {
+        self.0.as_mut_slice().zeroize();
+        sys::unlock_memory(self.0.as_slice());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SecretBytes;
+
+    #[test]
+    fn debug_doesnt_leak_the_secret() {
+        let secret = SecretBytes::new(b"\x21\x22".to_vec());
+        assert_eq!(format!("{:?}", secret), "SecretBytes(2 bytes)");
+        assert_eq!(secret.clone().as_slice(), b"\x21\x22");
+    }
+}
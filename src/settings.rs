@@ -0,0 +1,126 @@
+#![allow(clippy::needless_return)]
+
+use crate::{clipboard, totp};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+const SETTINGS_FILE_NAME: &str = "settings.json";
+
+/// User preferences persisted between runs, in "stip/settings.json" of the user configuration
+/// directory. Missing fields take their default value, so older files keep loading.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// Seconds added to the system clock when generating TOTP codes.
+    pub time_offset: i64,
+    /// Minutes without any input after which the vault is locked, 0 to never lock it.
+    pub auto_lock_minutes: u64,
+    /// Lock the vault when the window is minimized.
+    pub lock_on_minimize: bool,
+    /// Lock the vault when the screen of the session is locked.
+    pub lock_on_screen_lock: bool,
+    /// Seconds after which copied codes and secrets are cleared from the clipboard, 0 to keep
+    /// them.
+    pub clipboard_clear_seconds: u64,
+    /// Mask the codes until hovered or clicked, and the secrets until the master password is
+    /// entered, e.g. while sharing the screen.
+    pub privacy_mode: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        return Settings {
+            time_offset: 0,
+            auto_lock_minutes: 5,
+            lock_on_minimize: false,
+            lock_on_screen_lock: true,
+            clipboard_clear_seconds: 30,
+            privacy_mode: false,
+        };
+    }
+}
+
+fn config_dir() -> Option<PathBuf> {
+    cfg_if::cfg_if! {
+        if #[cfg(windows)] {
+            return std::env::var_os("APPDATA").map(PathBuf::from);
+        } else if #[cfg(target_os = "macos")] {
+            return std::env::var_os("HOME")
+                .map(|home| PathBuf::from(home).join("Library").join("Application Support"));
+        } else {
+            return std::env::var_os("XDG_CONFIG_HOME")
+                .map(PathBuf::from)
+                .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")));
+        }
+    }
+}
+
+fn settings_path() -> Option<PathBuf> {
+    return config_dir().map(|dir| dir.join("stip").join(SETTINGS_FILE_NAME));
+}
+
+impl Settings {
+    /// Load the settings, falling back to the defaults if the file doesn't exist or is invalid.
+    pub fn load() -> Self {
+        let path = match settings_path() {
+            Some(path) => path,
+            None => return Settings::default(),
+        };
+
+        let content = match std::fs::read(path.as_path()) {
+            Ok(content) => content,
+            Err(_) => return Settings::default(),
+        };
+
+        let settings: Settings = serde_json::from_slice(content.as_slice()).unwrap_or_else(|err| {
+            eprintln!("Ignoring invalid settings in {:?}, err: {}", path, err);
+            return Settings::default();
+        });
+        return settings.clamped();
+    }
+
+    /// Bring the values edited by hand in the file back in their range.
+    fn clamped(mut self) -> Self {
+        self.time_offset = self.time_offset.clamp(-totp::MAX_TIME_OFFSET, totp::MAX_TIME_OFFSET);
+        self.clipboard_clear_seconds = self.clipboard_clear_seconds.min(clipboard::MAX_CLEAR_SECONDS);
+        return self;
+    }
+
+    pub fn save(&self) -> Result<(), std::io::Error> {
+        let path = settings_path().ok_or_else(|| {
+            return std::io::Error::new(std::io::ErrorKind::NotFound, "no configuration directory");
+        })?;
+
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+
+        let content = serde_json::to_vec_pretty(self)?;
+        return std::fs::write(path, content);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Settings;
+
+    #[test]
+    fn missing_fields_take_their_default() {
+        let settings: Settings = serde_json::from_str(r#"{ "time_offset": 30 }"#).unwrap();
+        assert_eq!(settings.time_offset, 30);
+        assert_eq!(settings.auto_lock_minutes, 5);
+        assert!(!settings.lock_on_minimize);
+        assert!(settings.lock_on_screen_lock);
+        assert_eq!(settings.clipboard_clear_seconds, 30);
+        assert!(!settings.privacy_mode);
+    }
+
+    #[test]
+    fn out_of_range_values_are_clamped() {
+        let settings: Settings = serde_json::from_str(r#"{ "time_offset": -9999999999 }"#).unwrap();
+        assert_eq!(settings.clamped().time_offset, -crate::totp::MAX_TIME_OFFSET);
+
+        let settings: Settings = serde_json::from_str(r#"{ "clipboard_clear_seconds": 18446744073709551615 }"#).unwrap();
+        assert_eq!(settings.clamped().clipboard_clear_seconds, crate::clipboard::MAX_CLEAR_SECONDS);
+    }
+}
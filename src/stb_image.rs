@@ -61,6 +61,10 @@ pub fn load_from_memory(bytes: &[u8], channel: Channel) -> Result<Image, &'stati
         )
     };
 
+    if image.is_null() {
+        return Err("unsupported or corrupted image");
+    }
+
     let bytes_per_pixel = if channel == Channel::Default {
         channels_in_file as usize
     } else {
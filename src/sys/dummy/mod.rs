@@ -1,3 +1,16 @@
-pub fn capture_screen() -> Result<(usize, usize, Vec<u8>), ()> {
-    return Err(());
+pub fn capture_screen() -> Result<(usize, usize, Vec<u8>), String> {
+    return Err("capturing the screen isn't supported on this platform".to_string());
+}
+
+pub fn lock_memory(_data: &[u8]) {
+}
+
+pub fn unlock_memory(_data: &[u8]) {
+}
+
+pub fn disable_core_dumps() {
+}
+
+pub fn is_screen_locked() -> bool {
+    return false;
 }
@@ -0,0 +1,44 @@
+use core::ffi::c_void;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// Locking fails once the limit of locked memory is reached, which is only reported once.
+static LOCK_FAILURE_REPORTED: AtomicBool = AtomicBool::new(false);
+
+/// Lock the pages of `data` in physical memory, so they are never written to the swap.
+pub fn lock_memory(data: &[u8]) {
+    if data.is_empty() {
+        return;
+    }
+
+    let failed = unsafe { libc::mlock(data.as_ptr() as *const c_void, data.len()) } != 0;
+    if failed && !LOCK_FAILURE_REPORTED.swap(true, Ordering::Relaxed) {
+        eprintln!("mlock failed, err: {}", std::io::Error::last_os_error());
+    }
+}
+
+pub fn unlock_memory(data: &[u8]) {
+    if data.is_empty() {
+        return;
+    }
+
+    // Locks don't stack, so this also unlocks other buffers sharing these pages. They are then
+    // only protected by being wiped when dropped.
+    unsafe { libc::munlock(data.as_ptr() as *const c_void, data.len()) };
+}
+
+/// Prevent the process from writing core dumps, which would contain the secrets and passwords.
+/// This also prevents other processes of the user from attaching to it.
+pub fn disable_core_dumps() {
+    let limit = libc::rlimit {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+
+    if unsafe { libc::setrlimit(libc::RLIMIT_CORE, &limit) } != 0 {
+        eprintln!("setrlimit failed, err: {}", std::io::Error::last_os_error());
+    }
+
+    if unsafe { libc::prctl(libc::PR_SET_DUMPABLE, 0, 0, 0, 0) } != 0 {
+        eprintln!("prctl failed, err: {}", std::io::Error::last_os_error());
+    }
+}
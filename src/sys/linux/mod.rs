@@ -0,0 +1,18 @@
+mod memory;
+mod portal;
+mod session;
+mod x11;
+
+pub use memory::*;
+pub use session::*;
+
+/// Capture the screen with the xdg-desktop-portal on Wayland, where clients can't read the
+/// content of the screen, and with the X server otherwise. In both cases, the capture contains
+/// every monitor.
+pub fn capture_screen() -> Result<(usize, usize, Vec<u8>), String> {
+    if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+        return portal::capture_screen();
+    } else {
+        return x11::capture_screen();
+    }
+}
@@ -0,0 +1,75 @@
+use std::collections::HashMap;
+use url::Url;
+use uuid::Uuid;
+use zbus::blocking::{Connection, Proxy};
+use zbus::zvariant::{OwnedValue, Value};
+
+use crate::stb_image::{self, Channel};
+
+const PORTAL_DESTINATION: &str = "org.freedesktop.portal.Desktop";
+const PORTAL_PATH: &str = "/org/freedesktop/portal/desktop";
+
+/// Take a screenshot with the Screenshot interface of the xdg-desktop-portal. The portal writes
+/// the screenshot to a file and replies with its URI on a request object.
+pub fn capture_screen() -> Result<(usize, usize, Vec<u8>), String> {
+    let conn = Connection::session().map_err(|err| format!("failed to connect to the session bus, {}", err))?;
+
+    // The path of the request object is derived from the handle token, so we can listen to its
+    // response before calling the portal, otherwise the response could be missed.
+    let token = format!("stip_{}", Uuid::new_v4().simple());
+    let sender = conn
+        .unique_name()
+        .ok_or("the session bus didn't assign a name to the connection")?
+        .trim_start_matches(':')
+        .replace('.', "_");
+    let request_path = format!("{}/request/{}/{}", PORTAL_PATH, sender, token);
+
+    let request = Proxy::new(&conn, PORTAL_DESTINATION, request_path, "org.freedesktop.portal.Request")
+        .map_err(|err| format!("failed to create the portal request, {}", err))?;
+    let mut responses = request
+        .receive_signal("Response")
+        .map_err(|err| format!("failed to listen to the portal response, {}", err))?;
+
+    let mut options = HashMap::new();
+    options.insert("handle_token", Value::from(token.as_str()));
+    options.insert("interactive", Value::from(false));
+    conn.call_method(
+        Some(PORTAL_DESTINATION),
+        PORTAL_PATH,
+        Some("org.freedesktop.portal.Screenshot"),
+        "Screenshot",
+        &("", options),
+    )
+    .map_err(|err| format!("failed to call the screenshot portal, {}", err))?;
+
+    let response = responses.next().ok_or("the portal didn't respond")?;
+    let (code, results) = response
+        .body()
+        .deserialize::<(u32, HashMap<String, OwnedValue>)>()
+        .map_err(|err| format!("invalid response from the portal, {}", err))?;
+
+    if code != 0 {
+        return Err(format!("the screenshot was cancelled or denied, response {}", code));
+    }
+
+    let uri = results
+        .get("uri")
+        .and_then(|uri| <&str>::try_from(uri).ok())
+        .ok_or("the portal didn't return the URI of the screenshot")?;
+    let path = Url::parse(uri)
+        .ok()
+        .and_then(|uri| uri.to_file_path().ok())
+        .ok_or_else(|| format!("invalid screenshot URI '{}'", uri))?;
+
+    let content = std::fs::read(path.as_path()).map_err(|err| format!("{}: {}", path.display(), err))?;
+
+    // The screenshot was only taken to look for a QR code, so it isn't left behind.
+    if let Err(err) = std::fs::remove_file(path.as_path()) {
+        eprintln!("Failed to remove {:?}, err: {}", path, err);
+    }
+
+    let img = stb_image::load_from_memory(content.as_slice(), Channel::Grey)
+        .map_err(|err| format!("couldn't read the screenshot, {}", err))?;
+
+    return Ok((img.width, img.height, img.data().to_vec()));
+}
@@ -0,0 +1,37 @@
+use std::sync::OnceLock;
+use zbus::blocking::Connection;
+
+// The screen savers implementing the freedesktop interface, and GNOME which uses its own.
+const SCREEN_SAVERS: &[(&str, &str)] = &[
+    ("org.freedesktop.ScreenSaver", "/org/freedesktop/ScreenSaver"),
+    ("org.gnome.ScreenSaver", "/org/gnome/ScreenSaver"),
+];
+
+fn session_bus() -> Option<&'static Connection> {
+    static CONNECTION: OnceLock<Option<Connection>> = OnceLock::new();
+    return CONNECTION
+        .get_or_init(|| {
+            return Connection::session()
+                .map_err(|err| eprintln!("Failed to connect to the session bus, err: {}", err))
+                .ok();
+        })
+        .as_ref();
+}
+
+/// Whether the screen saver, which locks the screen, is active. Returns false when no screen
+/// saver answers on the session bus.
+pub fn is_screen_locked() -> bool {
+    let conn = match session_bus() {
+        Some(conn) => conn,
+        None => return false,
+    };
+
+    for (name, path) in SCREEN_SAVERS.iter().copied() {
+        let reply = conn.call_method(Some(name), path, Some(name), "GetActive", &());
+        if let Ok(active) = reply.and_then(|reply| reply.body().deserialize::<bool>()) {
+            return active;
+        }
+    }
+
+    return false;
+}
@@ -0,0 +1,46 @@
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::{ConnectionExt, ImageFormat};
+
+pub fn capture_screen() -> Result<(usize, usize, Vec<u8>), String> {
+    let (conn, screen_num) = x11rb::connect(None)
+        .map_err(|err| format!("failed to connect to the X server, {}", err))?;
+
+    // The root window spans every monitor of the screen.
+    let screen = &conn.setup().roots[screen_num];
+    let width = screen.width_in_pixels;
+    let height = screen.height_in_pixels;
+
+    let image = conn
+        .get_image(ImageFormat::Z_PIXMAP, screen.root, 0, 0, width, height, !0)
+        .map_err(|err| format!("GetImage failed, {}", err))?
+        .reply()
+        .map_err(|err| format!("GetImage failed, {}", err))?;
+
+    // Only 24 and 32 bits depths are supported, both stored with 4 bytes per pixel.
+    let width = width as usize;
+    let height = height as usize;
+    if image.data.len() != width * height * 4 {
+        return Err(format!("unsupported image of depth {}", image.depth));
+    }
+
+    let mut results = Vec::with_capacity(width * height);
+    for bgra in image.data.chunks_exact(4) {
+        let r = bgra[2] as f32;
+        let g = bgra[1] as f32;
+        let b = bgra[0] as f32;
+        let grey = (0.299 * r + 0.587 * g + 0.114 * b) as u8;
+        results.push(grey);
+    }
+
+    return Ok((width, height, results));
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    #[ignore = "requires an X server, e.g. `xvfb-run cargo test -- --ignored`"]
+    fn capture_root_window() {
+        let (width, height, data) = super::capture_screen().unwrap();
+        assert_eq!(data.len(), width * height);
+    }
+}
@@ -0,0 +1,34 @@
+use core::ffi::c_void;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use windows::Win32::System::Memory::{VirtualLock, VirtualUnlock};
+
+// Locking fails once the limit of locked memory is reached, which is only reported once.
+static LOCK_FAILURE_REPORTED: AtomicBool = AtomicBool::new(false);
+
+/// Lock the pages of `data` in physical memory, so they are never written to the page file.
+pub fn lock_memory(data: &[u8]) {
+    if data.is_empty() {
+        return;
+    }
+
+    if let Err(err) = unsafe { VirtualLock(data.as_ptr() as *const c_void, data.len()) } {
+        if !LOCK_FAILURE_REPORTED.swap(true, Ordering::Relaxed) {
+            eprintln!("VirtualLock failed, err: {:?}", err);
+        }
+    }
+}
+
+pub fn unlock_memory(data: &[u8]) {
+    if data.is_empty() {
+        return;
+    }
+
+    // Fails when another buffer on the same pages was already unlocked, which is harmless.
+    let _ = unsafe { VirtualUnlock(data.as_ptr() as *const c_void, data.len()) };
+}
+
+/// Windows doesn't write core dumps, crash dumps are only written when they are configured in
+/// Windows Error Reporting.
+pub fn disable_core_dumps() {
+}
@@ -1,2 +1,6 @@
+pub mod memory;
 pub mod screenshot;
+pub mod session;
+pub use memory::*;
 pub use screenshot::*; 
+pub use session::*;
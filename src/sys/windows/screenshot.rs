@@ -2,8 +2,10 @@ use core::ffi::c_void;
 
 use windows::Win32::{
     UI::WindowsAndMessaging::{
-        SM_CXSCREEN,
-        SM_CYSCREEN,
+        SM_XVIRTUALSCREEN,
+        SM_YVIRTUALSCREEN,
+        SM_CXVIRTUALSCREEN,
+        SM_CYVIRTUALSCREEN,
         GetSystemMetrics,
     },
     Graphics::Gdi::{
@@ -25,16 +27,20 @@ use windows::Win32::{
     Foundation::HWND,
 };
 
-fn get_primary_screen_dimensions() -> Result<(i32, i32), windows::core::Error> {
-    let width = unsafe { GetSystemMetrics(SM_CXSCREEN) };
+/// Position and dimensions of the virtual screen, the rectangle containing every monitor. The
+/// position is negative when a monitor is on the left or above the primary one.
+fn get_virtual_screen_rect() -> Result<(i32, i32, i32, i32), windows::core::Error> {
+    let x = unsafe { GetSystemMetrics(SM_XVIRTUALSCREEN) };
+    let y = unsafe { GetSystemMetrics(SM_YVIRTUALSCREEN) };
+    let width = unsafe { GetSystemMetrics(SM_CXVIRTUALSCREEN) };
     if width == 0 {
         return Err(windows::core::Error::from_win32());
     }
-    let height = unsafe { GetSystemMetrics(SM_CYSCREEN) };
+    let height = unsafe { GetSystemMetrics(SM_CYVIRTUALSCREEN) };
     if height == 0 {
         return Err(windows::core::Error::from_win32());
     }
-    return Ok((width, height));
+    return Ok((x, y, width, height));
 }
 
 struct ScreenDeviceContext(Option<HWND>, HDC);
@@ -75,20 +81,13 @@ fn create_compatible_dc(dc: HDC) -> Result<DeviceContext, windows::core::Error>
     return Ok(DeviceContext(new_dc));
 }
 
-pub fn capture_screen() -> Result<(usize, usize, Vec<u8>), ()> {
-    let src_dc = get_screen_dc().map_err(|err| {
-        eprintln!("get_screen_dc failed, err: {:?}", err);
-    })?;
-    let dst_dc = create_compatible_dc(src_dc.1).map_err(|err| {
-        eprintln!("create_compatible_dc failed, err: {:?}", err);
-        return ();
-    })?;
+pub fn capture_screen() -> Result<(usize, usize, Vec<u8>), String> {
+    let src_dc = get_screen_dc().map_err(|err| format!("GetDC failed, {}", err))?;
+    let dst_dc = create_compatible_dc(src_dc.1).map_err(|err| format!("CreateCompatibleDC failed, {}", err))?;
 
     let bytes_per_pixel = 4;
-    let (width, height) = get_primary_screen_dimensions().map_err(|err| {
-        eprintln!("get_primary_screen_dimensions failed, err: {:?}", err);
-        return ();
-    })?;
+    let (x, y, width, height) = get_virtual_screen_rect()
+        .map_err(|err| format!("GetSystemMetrics failed, {}", err))?;
 
     let bmi = BITMAPINFO {
         bmiHeader: BITMAPINFOHEADER {
@@ -111,16 +110,11 @@ pub fn capture_screen() -> Result<(usize, usize, Vec<u8>), ()> {
     let bitmap = unsafe {
         let ppvbits: *mut *mut c_void = &mut pixels_ptr as *mut *mut c_void;
         CreateDIBSection(Some(dst_dc.0), &bmi, DIB_RGB_COLORS, ppvbits, None, 0)
-    }.map_err(|err| {
-        eprintln!("CreateDIBSection failed, err: {:?}", err);
-        return ();
-    })?;
+    }.map_err(|err| format!("CreateDIBSection failed, {}", err))?;
 
     unsafe { SelectObject(dst_dc.0, bitmap.into()) };
-    unsafe { BitBlt(dst_dc.0, 0, 0, width, height, Some(src_dc.1), 0, 0, SRCCOPY) }.map_err(|err| {
-        eprintln!("BitBlt failed, err: {:?}", err);
-        return ();
-    })?;
+    unsafe { BitBlt(dst_dc.0, 0, 0, width, height, Some(src_dc.1), x, y, SRCCOPY) }
+        .map_err(|err| format!("BitBlt failed, {}", err))?;
 
     let width = width as usize;
     let height = height as usize;
@@ -0,0 +1,20 @@
+use windows::Win32::System::StationsAndDesktops::{
+    CloseDesktop,
+    DESKTOP_CONTROL_FLAGS,
+    DESKTOP_SWITCHDESKTOP,
+    OpenInputDesktop,
+    SwitchDesktop,
+};
+
+/// Whether the session is locked. The input desktop is then the secure desktop of the lock
+/// screen, which can't be opened or switched to by the user.
+pub fn is_screen_locked() -> bool {
+    let desktop = match unsafe { OpenInputDesktop(DESKTOP_CONTROL_FLAGS(0), false, DESKTOP_SWITCHDESKTOP) } {
+        Ok(desktop) => desktop,
+        Err(_) => return true,
+    };
+
+    let locked = unsafe { SwitchDesktop(desktop) }.is_err();
+    let _ = unsafe { CloseDesktop(desktop) };
+    return locked;
+}
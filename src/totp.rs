@@ -1,5 +1,6 @@
-use crate::sha1;
+use crate::hmac::{self, Algorithm};
 use serde::Serialize;
+use std::sync::atomic::{AtomicI64, Ordering};
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 const MOD_TABLE: [u32; 11] = [
@@ -16,33 +17,99 @@ const MOD_TABLE: [u32; 11] = [
     u32::MAX,
 ];
 
+/// Characters used by Steam Guard codes, which are 5 characters long rather than decimal digits.
+const STEAM_ALPHABET: &[u8] = b"23456789BCDFGHJKMNPQRTVWXY";
+const STEAM_DIGITS: usize = 5;
+
+// Seconds added to the system clock, to compensate machines whose clock drifted.
+static TIME_OFFSET: AtomicI64 = AtomicI64::new(0);
+
+/// Largest time offset, in either direction. A clock further off than a day is broken rather
+/// than drifting.
+pub const MAX_TIME_OFFSET: i64 = 24 * 60 * 60;
+
+/// Code of the current window, along with the code of the next one so it can be shown before
+/// the current one expires. `T` is `u32` for decimal codes and `String` for Steam Guard codes.
 #[derive(Serialize)]
-pub struct TotpToken {
-    pub number: u32,
+pub struct TotpToken<T = u32> {
+    pub number: T,
+    pub next_number: T,
     created_at: SystemTime,
     not_after: SystemTime,
 }
 
-pub fn from_moving_factor(secret: &[u8], moving_factor: u64, digits: usize) -> u32 {
+/// Dynamic truncation of the HMAC of `moving_factor`, as defined in RFC 4226, section 5.3.
+fn truncate(algorithm: Algorithm, secret: &[u8], moving_factor: u64) -> u32 {
     let moving_factor_bytes = moving_factor.to_be_bytes();
 
-    let mac = sha1::hmac(secret, &moving_factor_bytes);
-    let offset = (mac[19] & 0xf) as usize;
-    let number = (((mac[offset] & 0x7F) as u32) << 24)
+    // The dynamic truncation uses the low 4 bits of the last byte, whatever the digest size.
+    let mac = hmac::hmac(algorithm, secret, &moving_factor_bytes);
+    let offset = (mac[mac.len() - 1] & 0xf) as usize;
+    return (((mac[offset] & 0x7F) as u32) << 24)
         | (((mac[offset + 1] as u32) & 0xFF) << 16)
         | (((mac[offset + 2] as u32) & 0xFF) << 8)
         | ((mac[offset + 3] as u32) & 0xFF);
+}
 
+pub fn from_moving_factor(
+    algorithm: Algorithm,
+    secret: &[u8],
+    moving_factor: u64,
+    digits: usize,
+) -> u32 {
+    let number = truncate(algorithm, secret, moving_factor);
     let digits = std::cmp::min(digits, MOD_TABLE.len() - 1);
     return number % MOD_TABLE[digits];
 }
 
-pub fn from_seconds(secret: &[u8], timestamp: u64, digits: usize) -> u32 {
-    return from_moving_factor(secret, timestamp / 30, digits);
+pub fn steam_from_moving_factor(algorithm: Algorithm, secret: &[u8], moving_factor: u64) -> String {
+    let mut number = truncate(algorithm, secret, moving_factor) as usize;
+    let mut code = String::with_capacity(STEAM_DIGITS);
+    for _ in 0..STEAM_DIGITS {
+        code.push(STEAM_ALPHABET[number % STEAM_ALPHABET.len()] as char);
+        number /= STEAM_ALPHABET.len();
+    }
+    return code;
+}
+
+pub fn from_seconds(
+    algorithm: Algorithm,
+    secret: &[u8],
+    timestamp: u64,
+    period: u64,
+    digits: usize,
+) -> u32 {
+    return from_moving_factor(algorithm, secret, timestamp / period, digits);
 }
 
-pub fn from_now_with_period(secret: &[u8], period: u64, digits: usize) -> TotpToken {
-    let created_at = SystemTime::now();
+pub fn set_time_offset(seconds: i64) {
+    TIME_OFFSET.store(seconds.clamp(-MAX_TIME_OFFSET, MAX_TIME_OFFSET), Ordering::Relaxed);
+}
+
+pub fn time_offset() -> i64 {
+    return TIME_OFFSET.load(Ordering::Relaxed);
+}
+
+/// Current time of the system clock, corrected by the time offset. It saturates at the Unix
+/// epoch, which the windows are counted from.
+fn now() -> SystemTime {
+    let offset = time_offset();
+    let now = SystemTime::now();
+    let now = if offset < 0 {
+        now.checked_sub(Duration::from_secs(offset.unsigned_abs())).unwrap_or(UNIX_EPOCH)
+    } else {
+        now.checked_add(Duration::from_secs(offset as u64)).unwrap_or(now)
+    };
+    return now.max(UNIX_EPOCH);
+}
+
+/// Build the token of the current window, `generate` being called with a timestamp of the
+/// current window and then with a timestamp of the next one.
+fn from_now_with<T, F>(period: u64, generate: F) -> TotpToken<T>
+where
+    F: Fn(u64) -> T,
+{
+    let created_at = now();
     let seconds = created_at
         .duration_since(UNIX_EPOCH)
         .expect("Time went backwards")
@@ -53,37 +120,147 @@ pub fn from_now_with_period(secret: &[u8], period: u64, digits: usize) -> TotpTo
     let not_after = not_before
         .checked_add(Duration::new(period, 0))
         .expect("Couldn't create 'not_after'");
-    let number = from_seconds(secret, seconds, digits);
     return TotpToken {
-        number,
+        number: generate(seconds),
+        next_number: generate(seconds + period),
         created_at,
         not_after,
     };
 }
 
-pub fn progress() -> f32 {
-    let window = 30u128 * 1000;
-    let created_at = SystemTime::now();
-    let seconds = created_at
+pub fn from_now_with_period(
+    algorithm: Algorithm,
+    secret: &[u8],
+    period: u64,
+    digits: usize,
+) -> TotpToken {
+    return from_now_with(period, |timestamp| {
+        return from_seconds(algorithm, secret, timestamp, period, digits);
+    });
+}
+
+pub fn steam_from_now_with_period(
+    algorithm: Algorithm,
+    secret: &[u8],
+    period: u64,
+) -> TotpToken<String> {
+    return from_now_with(period, |timestamp| {
+        return steam_from_moving_factor(algorithm, secret, timestamp / period);
+    });
+}
+
+fn now_millis() -> u128 {
+    return now()
         .duration_since(UNIX_EPOCH)
         .expect("Time went backwards")
         .as_millis();
-    let min = (seconds / window) * window;
-    let offset = seconds - min;
-    return (offset as f32) / 30000.0;
+}
+
+/// Search the windows around the current one, up to `max_windows` away, for the one whose moving
+/// factor satisfies `is_match`, typically because it generates a code the server accepted. Returns
+/// the offset in seconds between that window and the current one, the closest window first.
+pub fn find_time_offset<F>(period: u64, max_windows: u64, is_match: F) -> Option<i64>
+where
+    F: Fn(u64) -> bool,
+{
+    let seconds = now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Time went backwards")
+        .as_secs();
+    let current = seconds / period;
+
+    for distance in 0..=max_windows {
+        if is_match(current + distance) {
+            return Some((distance * period) as i64);
+        }
+
+        if distance != 0 && distance <= current && is_match(current - distance) {
+            return Some(-((distance * period) as i64));
+        }
+    }
+
+    return None;
+}
+
+/// Fraction of the current window of `period` seconds that already elapsed, between 0 and 1.
+pub fn progress(period: u64) -> f32 {
+    let window = (period as u128) * 1000;
+    let offset = now_millis() % window;
+    return (offset as f32) / (window as f32);
+}
+
+/// Time left before the code of the current window of `period` seconds expires.
+pub fn remaining(period: u64) -> Duration {
+    let window = (period as u128) * 1000;
+    let left = window - (now_millis() % window);
+    return Duration::from_millis(left as u64);
 }
 
 #[cfg(test)]
 mod tests {
+    use super::Algorithm;
+
     #[test]
     fn works_with_specified_second() {
-        let number = super::from_seconds(b"\x21\x22", 1678732967, 6);
+        let number = super::from_seconds(Algorithm::Sha1, b"\x21\x22", 1678732967, 30, 6);
         assert_eq!(number, 486091);
     }
 
     #[test]
     fn support_very_large_digits() {
-        let number = super::from_seconds(b"\x21\x22", 1678732967, 32);
+        let number = super::from_seconds(Algorithm::Sha1, b"\x21\x22", 1678732967, 30, 32);
         assert_eq!(number, 783486091);
     }
+
+    #[test]
+    fn period_selects_the_window() {
+        let number = super::from_seconds(Algorithm::Sha1, b"\x21\x22", 1678732967, 60, 6);
+        assert_eq!(number, 356541);
+
+        let number = super::from_seconds(Algorithm::Sha1, b"\x21\x22", 1678732967, 35, 6);
+        assert_eq!(number, 26487);
+    }
+
+    #[test]
+    fn steam_codes() {
+        let code = super::steam_from_moving_factor(Algorithm::Sha1, b"\x21\x22", 1678732967 / 30);
+        assert_eq!(code.as_str(), "F43HX");
+
+        let code = super::steam_from_moving_factor(Algorithm::Sha1, b"12345678901234567890", 59 / 30);
+        assert_eq!(code.as_str(), "PV9M4");
+    }
+
+    #[test]
+    fn find_time_offset_in_neighbour_windows() {
+        // Such a large period makes it unlikely that the window changes during the test.
+        let period = 1_000_000_000;
+        let current = super::now_millis() as u64 / 1000 / period;
+
+        assert_eq!(super::find_time_offset(period, 3, |mf| mf == current), Some(0));
+        assert_eq!(super::find_time_offset(period, 3, |mf| mf == current + 2), Some(2 * period as i64));
+        assert_eq!(super::find_time_offset(period, 3, |mf| mf == current - 1), Some(-(period as i64)));
+        assert_eq!(super::find_time_offset(period, 3, |mf| mf == current + 4), None);
+    }
+
+    // Test vectors from RFC 6238, Appendix B.
+    static RFC6238_TESTS: &[(u64, u32, u32, u32)] = &[
+        (59, 94287082, 46119246, 90693936),
+        (1111111109, 7081804, 68084774, 25091201),
+        (1111111111, 14050471, 67062674, 99943326),
+        (1234567890, 89005924, 91819424, 93441116),
+        (2000000000, 69279037, 90698825, 38618901),
+        (20000000000, 65353130, 77737706, 47863826),
+    ];
+
+    #[test]
+    fn rfc6238_test_vectors() {
+        let sha1_secret = b"12345678901234567890";
+        let sha256_secret = b"12345678901234567890123456789012";
+        let sha512_secret = b"1234567890123456789012345678901234567890123456789012345678901234";
+        for (timestamp, sha1, sha256, sha512) in RFC6238_TESTS.iter().copied() {
+            assert_eq!(super::from_seconds(Algorithm::Sha1, sha1_secret, timestamp, 30, 8), sha1);
+            assert_eq!(super::from_seconds(Algorithm::Sha256, sha256_secret, timestamp, 30, 8), sha256);
+            assert_eq!(super::from_seconds(Algorithm::Sha512, sha512_secret, timestamp, 30, 8), sha512);
+        }
+    }
 }
@@ -2,32 +2,145 @@
 
 use std::path::{PathBuf, Path};
 use std::collections::HashMap;
+use std::time::Duration;
 use keepass::{
-    db::NodeRef,
+    config::{DatabaseConfig, KdfConfig},
+    db::{Entry, Group, Node, Value},
+    error::{DatabaseKeyError, DatabaseOpenError, DatabaseSaveError},
     Database,
     DatabaseKey,
 };
+use secstr::SecStr;
 use uuid::Uuid;
+use zeroize::Zeroizing;
 
-use crate::{otpauth, stb_image::{self, Channel, Image}};
+use crate::{archive, hmac::Algorithm, otpauth, totp, stb_image::{self, Channel, Image}};
 
 #[derive(Debug)]
-pub struct Error;
+pub enum Error {
+    /// Reading or writing the file at the path failed.
+    Io(PathBuf, std::io::Error),
+    /// The file couldn't be read from its zip archive.
+    Archive(archive::Error),
+    /// The file isn't an image that can be decoded.
+    ImageDecode(&'static str),
+    /// The image doesn't contain any QR code.
+    NoQrCode,
+    /// A QR code was detected, but couldn't be decoded.
+    QrDecode(rqrr::DeQRError),
+    /// The content of a QR code isn't a valid otpauth URL.
+    Url(otpauth::ParseError),
+    /// The secret of the entry, named by the string, can't be parsed.
+    InvalidEntry(String, otpauth::ParseError),
+    /// The password or the key file doesn't open the vault or the archive.
+    WrongPassword,
+    /// The archive is encrypted, but no password was given.
+    PasswordRequired,
+    /// The file isn't a KeePass database, or it's damaged.
+    CorruptDatabase(DatabaseOpenError),
+    /// The database couldn't be serialized.
+    Save(DatabaseSaveError),
+    /// The secret, named by the string, doesn't have an entry in the database.
+    MissingEntry(String),
+    /// The clipboard couldn't be read or written.
+    Clipboard(arboard::Error),
+    /// The screen couldn't be captured, for the given reason.
+    ScreenCapture(String),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return match self {
+            Error::Io(path, err) => write!(f, "{}: {}", path.display(), err),
+            Error::Archive(err) => write!(f, "{}", err),
+            Error::ImageDecode(err) => write!(f, "couldn't decode the image, {}", err),
+            Error::NoQrCode => write!(f, "no QR code found"),
+            Error::QrDecode(err) => write!(f, "couldn't decode the QR code, {}", err),
+            Error::Url(err) => write!(f, "invalid otpauth URL, {}", err),
+            Error::InvalidEntry(name, err) => write!(f, "the secret of '{}' is invalid, {}", name, err),
+            Error::WrongPassword => write!(f, "wrong password or key file"),
+            Error::PasswordRequired => write!(f, "a password is required"),
+            Error::CorruptDatabase(err) => write!(f, "invalid or corrupted database, {}", err),
+            Error::Save(err) => write!(f, "couldn't write the database, {}", err),
+            Error::MissingEntry(name) => write!(f, "'{}' isn't in the database", name),
+            Error::Clipboard(err) => write!(f, "{}", err),
+            Error::ScreenCapture(err) => write!(f, "couldn't capture the screen, {}", err),
+        };
+    }
+}
+
+// How many windows before and after the current one are searched when calibrating the clock.
+const MAX_CALIBRATION_WINDOWS: u64 = 20;
 
 pub struct Vault {
     pub path: PathBuf,
     pub database: Database,
+    key: DatabaseKey,
     pub custom_icons: Vec<Image>,
     custom_icons_idx: HashMap<Uuid, usize>,
 }
 
+/// Where the secret of an entry is stored, so edits are written back to the same place.
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum SecretField {
+    /// otpauth URL in the "URL" field.
+    Url,
+    /// otpauth URL in the "otp" attribute, as stored by KeePassXC.
+    Otp,
+    /// "TOTP Seed" and "TOTP Settings" attributes of legacy KeePass plugins.
+    Seed,
+}
+
+/// Group of the database, identified by its uuid as sibling groups can share the same name, which
+/// is only displayed. Groups that aren't in the database yet don't have a uuid.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct VaultGroup {
+    pub name: String,
+    pub uuid: Option<Uuid>,
+}
+
+impl VaultGroup {
+    /// Group found by its name, created if the database doesn't have it yet.
+    pub fn named(name: &str) -> Self {
+        return VaultGroup { name: name.to_string(), uuid: None };
+    }
+
+    fn from_group(group: &Group) -> Self {
+        return VaultGroup { name: group.name.clone(), uuid: Some(group.uuid) };
+    }
+}
+
 pub struct VaultSecret {
     pub name: String,
     parsed_url: otpauth::ParsedUrl,
     pub icon: Option<usize>,
+    /// Groups containing the entry, from the root group, which isn't included.
+    pub group: Vec<VaultGroup>,
+    entry: Option<Uuid>,
+    field: SecretField,
 }
 
 impl VaultSecret {
+    /// Secret that isn't stored in a vault yet, named after its issuer and account name.
+    pub fn from_parsed_url(parsed_url: otpauth::ParsedUrl) -> Self {
+        return VaultSecret {
+            name: format!("{}: {}", parsed_url.issuer, parsed_url.account_name),
+            parsed_url: parsed_url,
+            icon: None,
+            group: Vec::new(),
+            entry: None,
+            field: SecretField::Url,
+        };
+    }
+
+    fn from_grid<G: rqrr::BitGrid>(grid: &rqrr::Grid<G>) -> Result<Self, Error> {
+        // The content is the otpauth URL, which contains the secret.
+        let content = Zeroizing::new(grid.decode().map_err(Error::QrDecode)?.1);
+        let parsed_url = otpauth::ParsedUrl::parse(content.as_str()).map_err(Error::Url)?;
+
+        return Ok(Self::from_parsed_url(parsed_url));
+    }
+
     fn from_helper<F>(width: usize, height: usize, fill: F) -> Result<Self, Error>
     where
         F: FnMut(usize, usize) -> u8
@@ -35,55 +148,67 @@ impl VaultSecret {
         let mut img = rqrr::PreparedImage::prepare_from_greyscale(width, height, fill);
 
         if let Some(grid) = img.detect_grids().first() {
-            let content = grid
-                .decode()
-                .map_err(|err| {
-                    eprintln!("Failed to decode the QR code, error: {}", err);
-                    return Error;
-                })?
-                .1;
-
-            let parsed_url = otpauth::ParsedUrl::parse(content).map_err(|err| {
-                eprintln!("Failed to parse URL found in QR code, error: {:?}", err);
-                return Error;
-            })?;
-
-            return Ok(VaultSecret {
-                name: format!("{}: {}", parsed_url.issuer, parsed_url.account_name),
-                parsed_url: parsed_url,
-                icon: None,
-            });
+            return Self::from_grid(grid);
         } else {
-            eprintln!("Failed to detect the QR code");
-            return Err(Error);
+            return Err(Error::NoQrCode);
         }
     }
 
-    pub fn from_path(path: &Path) -> Result<Self, Error> {
-        let content = std::fs::read(path).map_err(|err| {
-            eprintln!("Failed to open {:?}, err: {}", path, err);
-            return Error;
-        })?;
+    /// Decode every QR code of the image, in the order they are detected. Each QR code succeeds
+    /// or fails on its own, and the result is empty when no QR code is detected.
+    fn all_from_helper<F>(width: usize, height: usize, fill: F) -> Vec<Result<Self, Error>>
+    where
+        F: FnMut(usize, usize) -> u8
+    {
+        let mut img = rqrr::PreparedImage::prepare_from_greyscale(width, height, fill);
 
-        let img = stb_image::load_from_memory(content.as_slice(), Channel::Grey).map_err(|err| {
-            eprintln!("Couldn't read the image {:?}, error: {}", path, err);
-            return Error;
+        return img.detect_grids().iter().map(Self::from_grid).collect();
+    }
+
+    fn load_image(path: &Path, password: Option<&str>) -> Result<Image, Error> {
+        let content = archive::read(path, password).map_err(|err| {
+            return match err {
+                archive::Error::Io(err) => Error::Io(path.to_path_buf(), err),
+                archive::Error::InvalidPassword => Error::WrongPassword,
+                archive::Error::PasswordRequired => Error::PasswordRequired,
+                err => Error::Archive(err),
+            };
         })?;
 
+        return stb_image::load_from_memory(content.as_slice(), Channel::Grey).map_err(Error::ImageDecode);
+    }
+
+    pub fn from_path(path: &Path, password: Option<&str>) -> Result<Self, Error> {
+        let img = Self::load_image(path, password)?;
         return Self::from_helper(img.width, img.height, |x, y| {
             return img.data()[(y * img.width) + x];
         });
     }
 
+    /// Every secret of the image at `path`, see [`VaultSecret::all_from_slice`].
+    pub fn all_from_path(path: &Path, password: Option<&str>) -> Result<Vec<Result<Self, Error>>, Error> {
+        let img = Self::load_image(path, password)?;
+        return Ok(Self::all_from_helper(img.width, img.height, |x, y| {
+            return img.data()[(y * img.width) + x];
+        }));
+    }
+
     pub fn from_slice(width: usize, height: usize, data: &[u8]) -> Result<Self, Error> {
-        println!("hey width: {}, height: {}", width, height);
         return Self::from_helper(width, height, |x, y| {
             return data[(y * width) + x];
         });
     }
 
+    /// Secrets of every QR code found in a greyscale image, such as an enrollment page or a
+    /// printed backup sheet.
+    pub fn all_from_slice(width: usize, height: usize, data: &[u8]) -> Vec<Result<Self, Error>> {
+        return Self::all_from_helper(width, height, |x, y| {
+            return data[(y * width) + x];
+        });
+    }
+
     pub fn url(&self) -> &str {
-        return self.parsed_url.raw.as_ref();
+        return self.parsed_url.raw.as_str();
     }
 
     pub fn secret(&self) -> &[u8] {
@@ -97,19 +222,263 @@ impl VaultSecret {
     pub fn digits(&self) -> usize {
         return self.parsed_url.digits;
     }
+
+    pub fn algorithm(&self) -> Algorithm {
+        return self.parsed_url.algorithm;
+    }
+
+    pub fn kind(&self) -> otpauth::OtpKind {
+        return self.parsed_url.kind;
+    }
+
+    pub fn counter(&self) -> u64 {
+        return self.parsed_url.counter;
+    }
+
+    pub fn encoder(&self) -> otpauth::Encoder {
+        return self.parsed_url.encoder;
+    }
+
+    /// Current code of the secret formatted for display, for HOTP secrets this is the code of
+    /// the current counter.
+    pub fn code(&self) -> String {
+        return self.codes().0;
+    }
+
+    /// Current and next code of the secret formatted for display.
+    pub fn codes(&self) -> (String, Option<String>) {
+        let format = |number: u32| format!("{:0digits$}", number, digits = self.digits());
+
+        if self.kind() == otpauth::OtpKind::Hotp {
+            return (self.code_at(self.counter()), None);
+        }
+
+        if self.encoder() == otpauth::Encoder::Steam {
+            let token = totp::steam_from_now_with_period(self.algorithm(), self.secret(), self.period());
+            return (token.number, Some(token.next_number));
+        }
+
+        let token = totp::from_now_with_period(self.algorithm(), self.secret(), self.period(), self.digits());
+        return (format(token.number), Some(format(token.next_number)));
+    }
+
+    fn code_at(&self, moving_factor: u64) -> String {
+        if self.encoder() == otpauth::Encoder::Steam {
+            return totp::steam_from_moving_factor(self.algorithm(), self.secret(), moving_factor);
+        }
+
+        let number = totp::from_moving_factor(self.algorithm(), self.secret(), moving_factor, self.digits());
+        return format!("{:0digits$}", number, digits = self.digits());
+    }
+
+    /// Find how many seconds the clock must be moved for `code`, a code accepted by the server,
+    /// to be the current code of this TOTP secret. The result is a multiple of the period.
+    pub fn find_time_offset(&self, code: &str) -> Option<i64> {
+        if self.kind() != otpauth::OtpKind::Totp {
+            return None;
+        }
+
+        let code = code.trim();
+        return totp::find_time_offset(self.period(), MAX_CALIBRATION_WINDOWS, |moving_factor| {
+            return self.code_at(moving_factor).eq_ignore_ascii_case(code);
+        });
+    }
+
+    /// Time left before the current code expires, `None` for HOTP secrets that don't expire.
+    pub fn remaining(&self) -> Option<Duration> {
+        return match self.kind() {
+            otpauth::OtpKind::Totp => Some(totp::remaining(self.period())),
+            otpauth::OtpKind::Hotp => None,
+        };
+    }
+
+    /// Whether `query` fuzzily matches the name, the issuer or the account name of the secret.
+    pub fn matches(&self, query: &str) -> bool {
+        return fuzzy_match(query, self.name.as_str())
+            || fuzzy_match(query, self.parsed_url.issuer.as_str())
+            || fuzzy_match(query, self.parsed_url.account_name.as_str());
+    }
+
+    /// Move a HOTP secret to its next code, the new counter is only persisted once the secret
+    /// is written back with [`Vault::update_secret`].
+    pub fn increment_counter(&mut self) {
+        let counter = self.parsed_url.counter.wrapping_add(1);
+        self.parsed_url.set_counter(counter);
+    }
+
+    /// Uuid of the KeePass entry this secret was read from, if any.
+    pub fn entry(&self) -> Option<Uuid> {
+        return self.entry;
+    }
+}
+
+/// Case-insensitive match of `query` as a subsequence of `text`, so "gh" matches "GitHub". Spaces
+/// of the query are ignored.
+fn fuzzy_match(query: &str, text: &str) -> bool {
+    let mut text = text.chars().flat_map(char::to_lowercase);
+    for expected in query.chars().filter(|ch| !ch.is_whitespace()).flat_map(char::to_lowercase) {
+        if !text.any(|ch| ch == expected) {
+            return false;
+        }
+    }
+
+    return true;
+}
+
+fn find_entry_mut(group: &mut Group, uuid: Uuid) -> Option<&mut Entry> {
+    for node in group.children.iter_mut() {
+        match node {
+            Node::Entry(entry) if entry.uuid == uuid => return Some(entry),
+            Node::Group(group) => {
+                if let Some(entry) = find_entry_mut(group, uuid) {
+                    return Some(entry);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    return None;
+}
+
+/// Fill `path` with the groups, below `group`, containing the entry `uuid`.
+fn find_entry_group(group: &Group, uuid: Uuid, path: &mut Vec<VaultGroup>) -> bool {
+    for node in group.children.iter() {
+        match node {
+            Node::Entry(entry) if entry.uuid == uuid => return true,
+            Node::Group(child) => {
+                path.push(VaultGroup::from_group(child));
+                if find_entry_group(child, uuid, path) {
+                    return true;
+                }
+                path.pop();
+            }
+            _ => (),
+        }
+    }
+
+    return false;
+}
+
+fn take_entry(group: &mut Group, uuid: Uuid) -> Option<Entry> {
+    let position = group.children.iter().position(|node| matches!(node, Node::Entry(entry) if entry.uuid == uuid));
+    if let Some(position) = position {
+        if let Node::Entry(entry) = group.children.remove(position) {
+            return Some(entry);
+        }
+    }
+
+    return group.children.iter_mut().find_map(|node| match node {
+        Node::Group(child) => take_entry(child, uuid),
+        _ => None,
+    });
+}
+
+/// Group at `path` below `group`, the missing groups being created. The groups without a uuid are
+/// found by name, and are given the uuid of the group found or created.
+fn group_at_mut<'a>(group: &'a mut Group, path: &mut [VaultGroup]) -> &'a mut Group {
+    let Some((first, rest)) = path.split_first_mut() else {
+        return group;
+    };
+
+    let position = group.children.iter().position(|node| match (node, first.uuid) {
+        (Node::Group(child), Some(uuid)) => child.uuid == uuid,
+        (Node::Group(child), None) => child.name == first.name,
+        _ => false,
+    });
+    let position = position.unwrap_or_else(|| {
+        group.add_child(Group::new(first.name.as_str()));
+        return group.children.len() - 1;
+    });
+
+    match &mut group.children[position] {
+        Node::Group(child) => {
+            first.uuid = Some(child.uuid);
+            return group_at_mut(child, rest);
+        }
+        Node::Entry(_) => unreachable!("The position of a group was expected"),
+    }
+}
+
+/// Read the secret of an entry, trying the layouts of KeePassXC, of the legacy plugins and then the
+/// otpauth URL in the "URL" field. Returns `None` if the entry doesn't have a secret at all.
+fn parse_entry_secret(
+    entry: &Entry,
+    title: &str,
+) -> Option<Result<(SecretField, otpauth::ParsedUrl), otpauth::ParseError>> {
+    if let Some(otp) = entry.get("otp").filter(|otp| !otp.is_empty()) {
+        return Some(otpauth::ParsedUrl::parse(otp).map(|url| (SecretField::Otp, url)));
+    }
+
+    if let Some(seed) = entry.get("TOTP Seed").filter(|seed| !seed.is_empty()) {
+        let settings = entry.get("TOTP Settings");
+        return Some(otpauth::ParsedUrl::from_seed(seed, settings, title).map(|url| (SecretField::Seed, url)));
+    }
+
+    // The URL field usually holds the address of a website, which isn't an invalid secret.
+    let url = entry
+        .get_url()
+        .filter(|url| url.starts_with("otpauth://") || url.starts_with("steam://"))?;
+    return Some(otpauth::ParsedUrl::parse(url).map(|url| (SecretField::Url, url)));
+}
+
+// Attributes holding a secret, always written protected as KeePassXC does.
+const SECRET_ATTRIBUTES: [&str; 2] = ["otp", "TOTP Seed"];
+
+fn set_field(entry: &mut Entry, key: &str, value: &str) -> bool {
+    if entry.get(key) == Some(value) {
+        return false;
+    }
+
+    // A field the user protected stays protected once rewritten.
+    let protected = SECRET_ATTRIBUTES.contains(&key) || matches!(entry.fields.get(key), Some(Value::Protected(_)));
+    let value = if protected {
+        Value::Protected(SecStr::new(value.as_bytes().to_vec()))
+    } else {
+        Value::Unprotected(value.to_string())
+    };
+
+    entry.fields.insert(key.to_string(), value);
+    return true;
+}
+
+fn backup_path(path: &Path) -> PathBuf {
+    let mut backup = path.as_os_str().to_os_string();
+    backup.push(".bak");
+    return PathBuf::from(backup);
+}
+
+fn temporary_path(path: &Path) -> PathBuf {
+    let mut tmp = path.as_os_str().to_os_string();
+    tmp.push(".tmp");
+    return PathBuf::from(tmp);
 }
 
 impl Vault {
-    pub fn open(path: PathBuf, password: &str) -> Result<Self, Error> {
-        let content = std::fs::read(path.as_path()).map_err(|err| {
-            eprintln!("Failed to open {:?}, err: {}", path.as_path(), err);
-            return Error;
-        })?;
+    /// Build the composite key of a database, made of a password, a key file, or both.
+    fn database_key(password: Option<&str>, keyfile: Option<&Path>) -> Result<DatabaseKey, Error> {
+        let mut key = DatabaseKey::new();
+        if let Some(password) = password {
+            key = key.with_password(password);
+        }
+
+        if let Some(keyfile) = keyfile {
+            let mut file = std::fs::File::open(keyfile).map_err(|err| Error::Io(keyfile.to_path_buf(), err))?;
+            key = key.with_keyfile(&mut file).map_err(|err| Error::Io(keyfile.to_path_buf(), err))?;
+        }
 
-        let key = DatabaseKey::new().with_password(password);
-        let database = Database::parse(content.as_slice(), key).map_err(|err| {
-            eprintln!("Failed to read database, err: {}", err);
-            return Error;
+        return Ok(key);
+    }
+
+    pub fn open(path: PathBuf, password: Option<&str>, keyfile: Option<&Path>) -> Result<Self, Error> {
+        let content = std::fs::read(path.as_path()).map_err(|err| Error::Io(path.clone(), err))?;
+
+        let key = Self::database_key(password, keyfile)?;
+        let database = Database::parse(content.as_slice(), key.clone()).map_err(|err| {
+            return match err {
+                DatabaseOpenError::Key(DatabaseKeyError::IncorrectKey) => Error::WrongPassword,
+                err => Error::CorruptDatabase(err),
+            };
         })?;
 
         let mut custom_icons: Vec<Image> = Vec::new();
@@ -126,46 +495,498 @@ impl Vault {
         return Ok(Vault {
             path,
             database,
+            key,
             custom_icons,
             custom_icons_idx,
         });
     }
 
-    pub fn secrets(&self) -> Vec<VaultSecret> {
+    /// Whether `password` and `keyfile` make the key the database was opened with, to confirm
+    /// the user before revealing a secret.
+    pub fn check_key(&self, password: Option<&str>, keyfile: Option<&Path>) -> bool {
+        return Self::database_key(password, keyfile).is_ok_and(|key| key == self.key);
+    }
+
+    /// Create an empty KDBX4 database at `path`, protected by `password`.
+    pub fn create(path: PathBuf, password: &str) -> Result<Self, Error> {
+        let mut config = DatabaseConfig::default();
+
+        // Use Argon2id with 64 MiB of memory, as KeePassXC does for new databases. The version is
+        // taken from the default configuration, as `keepass` doesn't re-export the argon2 crate.
+        if let KdfConfig::Argon2 { version, .. } = config.kdf_config {
+            config.kdf_config = KdfConfig::Argon2id {
+                iterations: 10,
+                memory: 64 * 1024 * 1024,
+                parallelism: 2,
+                version,
+            };
+        }
+
+        let vault = Vault {
+            path,
+            database: Database::new(config),
+            key: DatabaseKey::new().with_password(password),
+            custom_icons: Vec::new(),
+            custom_icons_idx: HashMap::new(),
+        };
+
+        vault.save()?;
+        return Ok(vault);
+    }
+
+    /// Secrets of every entry, along with the errors of the entries whose secret is invalid.
+    /// Entries without any secret, such as regular passwords, are skipped.
+    pub fn secrets(&self) -> (Vec<VaultSecret>, Vec<Error>) {
         let mut secrets = Vec::new();
-        for (idx, node) in self.database.root.iter().enumerate() {
-            if let NodeRef::Entry(entry) = node {
-                let title = entry.get_title().map(str::to_string).unwrap_or_else(|| format!("entry:{}", idx));
-
-                let custom_icon_idx = entry
-                    .custom_icon_uuid
-                    .map(|uuid| self.custom_icons_idx.get(&uuid).cloned())
-                    .flatten();
-
-                if let Some(url) = entry.get_url() {
-                    let parsed_url = match otpauth::ParsedUrl::parse(url.to_string()) {
-                        Ok(parsed_url) => parsed_url,
-                        Err(err) => {
-                            eprintln!(
-                                "Failed to parse URL found in QR code of '{}', error: {:?}",
-                                title,
-                                err,
-                            );
-                            continue;
-                        }
-                    };
-
-                    secrets.push(VaultSecret {
-                        name: title,
-                        parsed_url: parsed_url,
-                        icon: custom_icon_idx,
-                    });
-                } else {
-                    eprintln!("Skipping '{}', because the entry doesn't have an url", title);
+        let mut errors = Vec::new();
+        self.collect_secrets(&self.database.root, &mut Vec::new(), &mut secrets, &mut errors);
+        return (secrets, errors);
+    }
+
+    /// Depth first walk of `group`, `path` being the groups leading to it.
+    fn collect_secrets(
+        &self,
+        group: &Group,
+        path: &mut Vec<VaultGroup>,
+        secrets: &mut Vec<VaultSecret>,
+        errors: &mut Vec<Error>,
+    ) {
+        for (idx, node) in group.children.iter().enumerate() {
+            let entry = match node {
+                Node::Entry(entry) => entry,
+                Node::Group(child) => {
+                    path.push(VaultGroup::from_group(child));
+                    self.collect_secrets(child, path, secrets, errors);
+                    path.pop();
+                    continue;
                 }
+            };
+
+            let title = entry.get_title().map(str::to_string).unwrap_or_else(|| format!("entry:{}", idx));
+
+            let custom_icon_idx = entry
+                .custom_icon_uuid
+                .map(|uuid| self.custom_icons_idx.get(&uuid).cloned())
+                .flatten();
+
+            let (field, parsed_url) = match parse_entry_secret(entry, title.as_str()) {
+                Some(Ok(secret)) => secret,
+                Some(Err(err)) => {
+                    errors.push(Error::InvalidEntry(title, err));
+                    continue;
+                }
+                None => {
+                    eprintln!("Skipping '{}', because the entry doesn't have a secret", title);
+                    continue;
+                }
+            };
+
+            secrets.push(VaultSecret {
+                name: title,
+                parsed_url: parsed_url,
+                icon: custom_icon_idx,
+                group: path.clone(),
+                entry: Some(entry.uuid),
+                field,
+            });
+        }
+    }
+
+    /// Write the name, otpauth URL and group of `secret` back to the entry it was read from.
+    pub fn update_secret(&mut self, secret: &mut VaultSecret) -> Result<(), Error> {
+        let entry = secret
+            .entry
+            .and_then(|uuid| find_entry_mut(&mut self.database.root, uuid))
+            .ok_or_else(|| Error::MissingEntry(secret.name.clone()))?;
+        let uuid = entry.uuid;
+
+        let title_changed = set_field(entry, "Title", secret.name.as_str());
+        let url_changed = match secret.field {
+            SecretField::Url => set_field(entry, "URL", secret.url()),
+            SecretField::Otp => set_field(entry, "otp", secret.url()),
+            // The seed and settings can't change, as only TOTP secrets are stored this way.
+            SecretField::Seed => false,
+        };
+        if title_changed || url_changed {
+            entry.update_history();
+        }
+
+        let mut path = Vec::new();
+        if find_entry_group(&self.database.root, uuid, &mut path) && path != secret.group {
+            if let Some(entry) = take_entry(&mut self.database.root, uuid) {
+                group_at_mut(&mut self.database.root, secret.group.as_mut_slice()).add_child(entry);
             }
         }
 
-        return secrets;
+        return Ok(());
+    }
+
+    /// Add a new entry for `secret` in its group, created if needed, `secret` is then linked to
+    /// this entry.
+    pub fn add_secret(&mut self, secret: &mut VaultSecret) {
+        let mut entry = Entry::new();
+        set_field(&mut entry, "Title", secret.name.as_str());
+        set_field(&mut entry, "UserName", secret.parsed_url.account_name.as_str());
+        set_field(&mut entry, "URL", secret.url());
+
+        secret.entry = Some(entry.uuid);
+        secret.field = SecretField::Url;
+        group_at_mut(&mut self.database.root, secret.group.as_mut_slice()).add_child(entry);
+    }
+
+    /// Write the database to `path`. The database is first written to a temporary file which then
+    /// replaces the previous file, so a failure never leaves a truncated database behind, and the
+    /// previous file is kept next to it with the ".bak" extension.
+    pub fn save(&self) -> Result<(), Error> {
+        let tmp_path = temporary_path(self.path.as_path());
+        let mut file = std::fs::File::create(tmp_path.as_path()).map_err(|err| Error::Io(tmp_path.clone(), err))?;
+
+        let written = self
+            .database
+            .save(&mut file, self.key.clone())
+            .map_err(Error::Save)
+            .and_then(|_| file.sync_all().map_err(|err| Error::Io(tmp_path.clone(), err)));
+
+        drop(file);
+        if written.is_err() {
+            let _ = std::fs::remove_file(tmp_path.as_path());
+            return written;
+        }
+
+        if self.path.exists() {
+            let backup_path = backup_path(self.path.as_path());
+            std::fs::copy(self.path.as_path(), backup_path.as_path())
+                .map_err(|err| Error::Io(backup_path.clone(), err))?;
+        }
+
+        std::fs::rename(tmp_path.as_path(), self.path.as_path()).map_err(|err| Error::Io(self.path.clone(), err))?;
+
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn secret_from_image() {
+        let secret = VaultSecret::from_path(Path::new("tests/data/noreply.example.png"), None).unwrap();
+        assert_eq!(secret.name.as_str(), "BigTech: noreply@example.com");
+    }
+
+    #[test]
+    fn every_secret_of_an_image() {
+        let img = stb_image::load_from_memory(
+            std::fs::read("tests/data/noreply.example.png").unwrap().as_slice(),
+            Channel::Grey,
+        )
+        .unwrap();
+
+        // Two copies of the QR code side by side, separated by a white margin.
+        let width = img.width * 2 + 32;
+        let mut data = vec![255u8; width * img.height];
+        for y in 0..img.height {
+            let row = &img.data()[(y * img.width)..((y + 1) * img.width)];
+            data[(y * width)..(y * width + img.width)].copy_from_slice(row);
+            data[(y * width + img.width + 32)..((y + 1) * width)].copy_from_slice(row);
+        }
+
+        let secrets = VaultSecret::all_from_slice(width, img.height, data.as_slice());
+        assert_eq!(secrets.len(), 2);
+        for secret in secrets {
+            assert_eq!(secret.unwrap().name.as_str(), "BigTech: noreply@example.com");
+        }
+
+        let blank = vec![255u8; 64 * 64];
+        assert!(VaultSecret::all_from_slice(64, 64, blank.as_slice()).is_empty());
+    }
+
+    #[test]
+    fn fuzzy_matching() {
+        assert!(fuzzy_match("", "GitHub"));
+        assert!(fuzzy_match("gh", "GitHub"));
+        assert!(fuzzy_match("GITHUB", "github"));
+        assert!(fuzzy_match("aws prod", "AWS (production)"));
+        assert!(!fuzzy_match("hg", "GitHub"));
+        assert!(!fuzzy_match("gitlab", "GitHub"));
+    }
+
+    #[test]
+    fn secret_matches_issuer_and_account() {
+        let mut secret = VaultSecret::from_path(Path::new("tests/data/noreply.example.png"), None).unwrap();
+        secret.name = "Work".to_string();
+        assert!(secret.matches("work"));
+        assert!(secret.matches("bigtech"));
+        assert!(secret.matches("noreply"));
+        assert!(!secret.matches("github"));
+    }
+
+    /// Database in the temporary directory, deleted along with its backup when dropped, so the
+    /// files don't leak when an assert fails.
+    struct TempDatabase {
+        path: PathBuf,
+    }
+
+    impl TempDatabase {
+        /// Path of a database that doesn't exist yet, `name` telling apart the tests.
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!("stip-{}-{}.kdbx", name, Uuid::new_v4()));
+            return TempDatabase { path };
+        }
+
+        fn open(&self) -> Vault {
+            return Vault::open(self.path.clone(), Some("password"), None).unwrap();
+        }
+    }
+
+    impl Drop for TempDatabase {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(self.path.as_path());
+            let _ = std::fs::remove_file(backup_path(self.path.as_path()));
+        }
+    }
+
+    fn entry(fields: &[(&str, &str)]) -> Entry {
+        let mut entry = Entry::new();
+        for (key, value) in fields.iter() {
+            set_field(&mut entry, key, value);
+        }
+        return entry;
+    }
+
+    fn save_database(name: &str, database: &Database, key: DatabaseKey) -> TempDatabase {
+        let temp = TempDatabase::new(name);
+        let mut file = std::fs::File::create(temp.path.as_path()).unwrap();
+        database.save(&mut file, key).unwrap();
+        return temp;
+    }
+
+    /// Database protected by the password "password", with an entry per list of fields in the
+    /// root group.
+    fn create_database(name: &str, entries: &[&[(&str, &str)]]) -> TempDatabase {
+        let mut database = Database::new(Default::default());
+        for fields in entries {
+            database.root.add_child(entry(fields));
+        }
+
+        return save_database(name, &database, DatabaseKey::new().with_password("password"));
+    }
+
+    #[test]
+    fn check_key_of_the_opened_database() {
+        let temp = create_database("key", &[]);
+
+        let vault = temp.open();
+        assert!(vault.check_key(Some("password"), None));
+        assert!(!vault.check_key(Some("wrong"), None));
+        assert!(!vault.check_key(None, None));
+    }
+
+    #[test]
+    fn hotp_counter_is_saved() {
+        let temp = create_database(
+            "hotp",
+            &[&[
+                ("Title", "hotp"),
+                ("URL", "otpauth://hotp/Company:name?secret=gkjeixzp5xmm37meoimq====&counter=7"),
+            ]],
+        );
+
+        let mut vault = temp.open();
+        let mut secret = vault.secrets().0.pop().unwrap();
+        assert_eq!(secret.counter(), 7);
+        secret.increment_counter();
+        vault.update_secret(&mut secret).unwrap();
+        vault.save().unwrap();
+
+        let secret = temp.open().secrets().0.pop().unwrap();
+        assert_eq!(secret.counter(), 8);
+    }
+
+    #[test]
+    fn secrets_from_keepassxc_attributes() {
+        let temp = create_database(
+            "keepassxc",
+            &[
+                &[
+                    ("Title", "keepassxc"),
+                    ("URL", "https://example.com"),
+                    ("otp", "otpauth://totp/Company:name?secret=gkjeixzp5xmm37meoimq====&period=60"),
+                ],
+                &[("Title", "legacy"), ("TOTP Seed", "GKJE IXZP 5XMM 37ME OIMQ"), ("TOTP Settings", "30;S")],
+                &[("Title", "url"), ("URL", "otpauth://totp/Company:name?secret=gkjeixzp5xmm37meoimq====")],
+                &[("Title", "website"), ("URL", "https://example.com")],
+                &[("Title", "invalid"), ("otp", "otpauth://totp/Company:name")],
+            ],
+        );
+
+        let (secrets, errors) = temp.open().secrets();
+        assert!(matches!(errors.as_slice(), [Error::InvalidEntry(name, _)] if name == "invalid"));
+        assert_eq!(secrets.len(), 3);
+        assert_eq!(secrets[0].name.as_str(), "keepassxc");
+        assert_eq!(secrets[0].period(), 60);
+        assert_eq!(secrets[0].field, SecretField::Otp);
+        assert_eq!(secrets[1].name.as_str(), "legacy");
+        assert_eq!(secrets[1].encoder(), otpauth::Encoder::Steam);
+        assert_eq!(secrets[1].field, SecretField::Seed);
+        assert_eq!(secrets[2].name.as_str(), "url");
+        assert_eq!(secrets[2].field, SecretField::Url);
+    }
+
+    #[test]
+    fn secret_fields_stay_protected() {
+        let mut entry = Entry::new();
+        entry.fields.insert("URL".to_string(), Value::Protected(SecStr::new(b"otpauth://".to_vec())));
+        set_field(&mut entry, "URL", "otpauth://totp/Company:name?secret=gkjeixzp5xmm37meoimq====");
+        set_field(&mut entry, "otp", "otpauth://totp/Company:name?secret=gkjeixzp5xmm37meoimq====");
+        set_field(&mut entry, "Title", "name");
+
+        assert!(matches!(entry.fields.get("URL"), Some(Value::Protected(_))));
+        assert!(matches!(entry.fields.get("otp"), Some(Value::Protected(_))));
+        assert!(matches!(entry.fields.get("Title"), Some(Value::Unprotected(_))));
+    }
+
+    #[test]
+    fn entries_keep_and_change_their_group() {
+        let url = "otpauth://totp/Company:name?secret=gkjeixzp5xmm37meoimq====";
+
+        let mut database = Database::new(Default::default());
+        let mut work = Group::new("Work");
+        let mut aws = Group::new("AWS");
+        aws.add_child(entry(&[("Title", "aws"), ("URL", url)]));
+        work.add_child(aws);
+        database.root.add_child(work);
+        database.root.add_child(entry(&[("Title", "root"), ("URL", url)]));
+        let temp = save_database("groups", &database, DatabaseKey::new().with_password("password"));
+
+        let mut vault = temp.open();
+        let mut secrets = vault.secrets().0;
+        assert_eq!(secrets[0].name.as_str(), "aws");
+        let names = secrets[0].group.iter().map(|group| group.name.as_str()).collect::<Vec<&str>>();
+        assert_eq!(names, ["Work", "AWS"]);
+        assert!(secrets[1].group.is_empty());
+
+        secrets[1].group = vec![VaultGroup::named("Personal")];
+        vault.update_secret(&mut secrets[1]).unwrap();
+        assert!(secrets[1].group[0].uuid.is_some());
+        secrets[0].group.pop();
+        vault.update_secret(&mut secrets[0]).unwrap();
+        vault.save().unwrap();
+
+        let secrets = temp.open().secrets().0;
+        let groups = secrets
+            .iter()
+            .map(|secret| (secret.name.as_str(), secret.group.iter().map(|group| group.name.as_str()).collect()))
+            .collect::<Vec<(&str, Vec<&str>)>>();
+        assert_eq!(groups, [("aws", vec!["Work"]), ("root", vec!["Personal"])]);
+    }
+
+    #[test]
+    fn sibling_groups_with_the_same_name_stay_apart() {
+        let url = "otpauth://totp/Company:name?secret=gkjeixzp5xmm37meoimq====";
+
+        let mut database = Database::new(Default::default());
+        for title in ["first", "second"] {
+            let mut work = Group::new("Work");
+            work.add_child(entry(&[("Title", title), ("URL", url)]));
+            database.root.add_child(work);
+        }
+        let temp = save_database("siblings", &database, DatabaseKey::new().with_password("password"));
+
+        let mut vault = temp.open();
+        let mut secrets = vault.secrets().0;
+        assert_ne!(secrets[0].group, secrets[1].group);
+
+        // Moving the first entry to the second group must not pick the first group named "Work".
+        secrets[0].group = secrets[1].group.clone();
+        vault.update_secret(&mut secrets[0]).unwrap();
+        vault.save().unwrap();
+
+        let secrets = temp.open().secrets().0;
+        assert_eq!(secrets.len(), 2);
+        assert_eq!(secrets[0].group, secrets[1].group);
+    }
+
+    #[test]
+    fn created_vault_can_be_opened() {
+        let temp = TempDatabase::new("create");
+        let mut vault = Vault::create(temp.path.clone(), "password").unwrap();
+        let mut secret = VaultSecret::from_path(Path::new("tests/data/noreply.example.png"), None).unwrap();
+        vault.add_secret(&mut secret);
+        vault.save().unwrap();
+
+        assert_eq!(temp.open().secrets().0.len(), 1);
+    }
+
+    #[test]
+    fn errors_tell_what_failed() {
+        let temp = create_database("errors", &[]);
+        let wrong_password = Vault::open(temp.path.clone(), Some("wrong"), None);
+        assert!(matches!(wrong_password, Err(Error::WrongPassword)));
+
+        let image = PathBuf::from("tests/data/noreply.example.png");
+        assert!(matches!(Vault::open(image, Some("password"), None), Err(Error::CorruptDatabase(_))));
+
+        let missing = Path::new("tests/data/missing.png");
+        assert!(matches!(VaultSecret::from_path(missing, None), Err(Error::Io(..))));
+
+        let not_an_image = Path::new("Cargo.toml");
+        assert!(matches!(VaultSecret::from_path(not_an_image, None), Err(Error::ImageDecode(_))));
+
+        let encrypted = Path::new("tests/data/encrypted-with-password-noreply.example.zip/noreply.example.png");
+        assert!(matches!(VaultSecret::from_path(encrypted, Some("wrong")), Err(Error::WrongPassword)));
+        assert!(matches!(VaultSecret::from_path(encrypted, None), Err(Error::PasswordRequired)));
+
+        assert!(matches!(VaultSecret::from_slice(4, 4, &[255; 16]), Err(Error::NoQrCode)));
+    }
+
+    #[test]
+    fn open_with_composite_key() {
+        let keyfile = Path::new("tests/vault/file.txt");
+        let mut file = std::fs::File::open(keyfile).unwrap();
+        let key = DatabaseKey::new().with_password("password").with_keyfile(&mut file).unwrap();
+        let temp = save_database("keyfile", &Database::new(Default::default()), key);
+
+        assert!(Vault::open(temp.path.clone(), Some("password"), Some(keyfile)).is_ok());
+        assert!(Vault::open(temp.path.clone(), Some("password"), None).is_err());
+        assert!(Vault::open(temp.path.clone(), None, Some(keyfile)).is_err());
+    }
+
+    #[test]
+    fn edits_are_saved_with_a_backup() {
+        let temp = create_database(
+            "edits",
+            &[&[("Title", "before"), ("URL", "otpauth://totp/Company:name?secret=gkjeixzp5xmm37meoimq====")]],
+        );
+
+        let mut vault = temp.open();
+        let mut renamed = vault.secrets().0.pop().unwrap();
+        renamed.name = "after".to_string();
+        vault.update_secret(&mut renamed).unwrap();
+
+        let mut added = VaultSecret::from_path(Path::new("tests/data/noreply.example.png"), None).unwrap();
+        vault.add_secret(&mut added);
+        assert!(added.entry().is_some());
+        vault.save().unwrap();
+
+        let names = temp.open().secrets().0.into_iter().map(|secret| secret.name).collect::<Vec<String>>();
+        let backup_names = Vault::open(backup_path(temp.path.as_path()), Some("password"), None)
+            .unwrap()
+            .secrets()
+            .0
+            .into_iter()
+            .map(|secret| secret.name)
+            .collect::<Vec<String>>();
+
+        assert_eq!(names, ["after", "BigTech: noreply@example.com"]);
+        assert_eq!(backup_names, ["before"]);
+    }
+
+    #[test]
+    fn secret_from_encrypted_archive() {
+        let path = Path::new("tests/data/encrypted-with-password-noreply.example.zip/noreply.example.png");
+        let secret = VaultSecret::from_path(path, Some("password")).unwrap();
+        assert_eq!(secret.name.as_str(), "BigTech: noreply@example.com");
     }
 }